@@ -0,0 +1,119 @@
+use rltk::{Rltk, Point, RandomNumberGenerator};
+use specs::prelude::*;
+use super::{Map, gui::PANEL_HEIGHT, draw};
+
+/*The part of the map that fits on the screen, in map coordinates. max_x and max_y are one past the last tile shown*/
+#[derive(Copy, Clone)]
+pub struct Viewport {
+    pub min_x: i32,
+    pub max_x: i32,
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+impl Viewport {
+    pub fn to_screen(self, x: i32, y: i32) -> Option<(i32, i32)> { /*Where a map tile is drawn, or None if it is off screen*/
+        if x < self.min_x || x >= self.max_x || y < self.min_y || y >= self.max_y { return None; }
+        Some((x - self.min_x, y - self.min_y))
+    }
+
+    pub fn to_world(self, screen_x: i32, screen_y: i32) -> Option<Point> { /*Which map tile is under a screen position, or None if it is over the UI panel*/
+        if screen_x < 0 || screen_x >= self.max_x - self.min_x || screen_y < 0 || screen_y >= self.max_y - self.min_y { return None; }
+        Some(Point::new(screen_x + self.min_x, screen_y + self.min_y))
+    }
+}
+
+const SHAKE_MS : f32 = 300.0;
+const SHAKE_THRESHOLD : f32 = 0.2; /*Hits that take less than this much of the player's max hp don't shake anything*/
+const MAX_SHAKE : f32 = 3.0; /*Tiles*/
+
+/*Jolts the map around for a moment after the player takes a big hit. The UI panel is drawn on its own, so it stays put.
+Has its own random numbers, so shaking never changes how the dungeon rolls*/
+pub struct ScreenShake {
+    pub ms_remaining: f32,
+    pub intensity: f32, /*How many tiles the map can be thrown at the start of the shake*/
+    offset: (i32, i32),
+    rng: RandomNumberGenerator,
+}
+
+impl ScreenShake {
+    pub fn new() -> ScreenShake {
+        ScreenShake { ms_remaining: 0.0, intensity: 0.0, offset: (0, 0), rng: RandomNumberGenerator::new() }
+    }
+
+    /*Called when the player loses damage hp. Bigger hits shake harder, and a new shake never weakens one already going*/
+    pub fn hit(&mut self, damage: i32, max_hp: i32) {
+        let fraction = damage as f32 / i32::max(1, max_hp) as f32;
+        if fraction < SHAKE_THRESHOLD { return; }
+        self.intensity = f32::max(self.current_intensity(), f32::min(MAX_SHAKE, fraction / SHAKE_THRESHOLD));
+        self.ms_remaining = SHAKE_MS;
+    }
+
+    fn current_intensity(&self) -> f32 { /*Dies away over the length of the shake*/
+        self.intensity * self.ms_remaining / SHAKE_MS
+    }
+
+    /*Once a frame, so everything drawn in the frame is thrown the same way*/
+    pub fn update(&mut self, frame_time_ms: f32) {
+        self.ms_remaining = f32::max(0.0, self.ms_remaining - frame_time_ms);
+        let reach = self.current_intensity().round() as i32;
+        self.offset = if reach > 0 { (self.rng.range(-reach, reach + 1), self.rng.range(-reach, reach + 1)) } else { (0, 0) };
+    }
+
+    fn apply(&self, viewport: Viewport) -> Viewport { /*Knocks the viewport off by this frame's offset. Only for drawing the map, never for working out what the mouse is over*/
+        let (shake_x, shake_y) = self.offset;
+        Viewport { min_x: viewport.min_x + shake_x, max_x: viewport.max_x + shake_x, min_y: viewport.min_y + shake_y, max_y: viewport.max_y + shake_y }
+    }
+}
+
+/*Works out which part of the map to show. The player stays in the middle of the screen, except near the edges of the map where the camera stops scrolling.
+This is where the camera really is, screen shake or not, so the mouse always points at the tile it looks like it is over*/
+pub fn get_screen_bounds(ecs: &World, ctx: &Rltk) -> Viewport {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let (screen_width, screen_height) = ctx.get_char_size();
+    let view_width = screen_width as i32;
+    let view_height = screen_height as i32 - PANEL_HEIGHT; /*The panel covers the bottom of the screen*/
+
+    let min_x = (player_pos.x - view_width / 2).clamp(0, i32::max(0, map.width - view_width));
+    let min_y = (player_pos.y - view_height / 2).clamp(0, i32::max(0, map.height - view_height));
+    Viewport { min_x, max_x: min_x + view_width, min_y, max_y: min_y + view_height }
+}
+
+/*Draws the map and everything on it through the camera, thrown about by any screen shake*/
+pub fn render_camera(ecs: &World, ctx: &mut Rltk, show_threat: bool) {
+    let viewport = ecs.fetch::<ScreenShake>().apply(get_screen_bounds(ecs, ctx));
+    draw::draw_map(ecs, ctx, &viewport); /*Map first, so entities end up on top of it*/
+    draw::draw_entities(ecs, ctx, &viewport, show_threat);
+    draw::draw_floating_text(ecs, ctx, &viewport);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewport_only_maps_tiles_inside_it() {
+        let viewport = Viewport { min_x: 10, max_x: 30, min_y: 5, max_y: 15 };
+        assert_eq!(viewport.to_screen(10, 5), Some((0, 0)));
+        assert_eq!(viewport.to_screen(29, 14), Some((19, 9)));
+        for (x, y) in [(9, 5), (30, 5), (10, 4), (10, 15), (30, 15)] { /*One past each edge*/
+            assert_eq!(viewport.to_screen(x, y), None, "{},{}", x, y);
+        }
+
+        assert_eq!(viewport.to_world(0, 0), Some(Point::new(10, 5)));
+        assert_eq!(viewport.to_world(19, 9), Some(Point::new(29, 14)));
+        for (x, y) in [(-1, 0), (20, 0), (0, -1), (0, 10)] {
+            assert_eq!(viewport.to_world(x, y), None, "{},{}", x, y);
+        }
+    }
+
+    #[test]
+    fn shaking_moves_only_the_drawn_viewport() {
+        let viewport = Viewport { min_x: 10, max_x: 30, min_y: 5, max_y: 15 };
+        let mut shake = ScreenShake::new();
+        shake.offset = (2, -1);
+        let shaken = shake.apply(viewport);
+        assert_eq!(shaken.to_screen(12, 4), Some((0, 0)));
+    }
+}
@@ -0,0 +1,462 @@
+use specs::prelude::*;
+use specs_derive::*;
+use specs::saveload::{Marker, ConvertSaveload};
+use specs::error::NoError;
+use serde::{Serialize, Deserialize};
+use rltk::RGB;
+
+/*Derive is a library short-hand for implementing the desired interface for that struct. So position is a component (building block) for entities such as players*/
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+#[storage(FlaggedStorage)] /*Says when an entity gains or loses a position, so the map index knows to rebuild*/
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Renderable {
+    pub glyph: rltk::FontCharType,
+    pub fg: RGB,
+    pub bg: RGB,
+    pub render_order: i32, /*Lower numbers are drawn last, so they end up on top. 0 is the player, 1 monsters, 2 items*/
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct LeftMover {
+
+}
+ 
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Player {
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Viewshed { /*What an entity can see. dirty is set whenever it moves, so the visibility system knows to recompute it*/
+    pub visible_tiles: Vec<rltk::Point>,
+    pub range: i32,
+    pub dirty: bool,
+}
+
+impl Viewshed {
+    /*The map index of every tile in view, each one only once, skipping anything off the map. Saves every caller doing its own index and bounds checks*/
+    pub fn visible_indices<'a>(&'a self, map: &'a super::map::Map) -> impl Iterator<Item = usize> + 'a {
+        let mut seen = std::collections::HashSet::new();
+        self.visible_tiles.iter()
+            .filter(move |p| map.in_bounds(p.x, p.y))
+            .map(move |p| map.xy_idx(p.x, p.y))
+            .filter(move |idx| seen.insert(*idx))
+    }
+}
+
+/*What kind of hurt a hit does. Some things shrug off a kind of damage, others take extra from it*/
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Cold,
+}
+
+impl DamageType {
+    pub fn name(self) -> &'static str {
+        match self {
+            DamageType::Physical => "blow",
+            DamageType::Fire => "fire",
+            DamageType::Cold => "cold",
+        }
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct CombatStats { /*Anything that can fight has these*/
+    pub max_hp: i32,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    #[serde(default)]
+    pub resistances: Vec<DamageType>, /*Takes half damage from these*/
+    #[serde(default)]
+    pub weaknesses: Vec<DamageType>, /*and double from these*/
+}
+
+impl CombatStats {
+    pub fn adjusted_damage(&self, amount: i32, damage_type: DamageType) -> i32 { /*How much a hit of this type really does*/
+        if self.resistances.contains(&damage_type) {
+            amount / 2
+        } else if self.weaknesses.contains(&damage_type) {
+            amount * 2
+        } else {
+            amount
+        }
+    }
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToMelee { /*An intent to attack. Attached when something decides to attack, and processed by the melee combat system*/
+    pub target: Entity,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct SufferDamage { /*Damage queued up for an entity this turn. It is a vector since several things can hit the same entity in one turn*/
+    pub amount: Vec<(i32, DamageType)>,
+}
+
+impl SufferDamage {
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32, damage_type: DamageType) {
+        if let Some(suffering) = store.get_mut(victim) { /*Already taking damage this turn, so add to the pile*/
+            suffering.amount.push((amount, damage_type));
+        } else {
+            let dmg = SufferDamage { amount: vec![(amount, damage_type)] };
+            store.insert(victim, dmg).expect("Unable to insert damage");
+        }
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Monster { /*Marks an entity as being controlled by the monster AI*/
+    #[serde(default)]
+    pub fleeing: bool, /*Badly hurt, so running from the player instead of chasing them*/
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Faction { /*Which side a creature is on. The FactionTable says how each side treats the others*/
+    pub name: String,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct BlocksVisibility { /*Nothing can see through the tile this is on. It doesn't stop anything walking there, that's BlocksTile*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Door { /*Closed doors block movement and sight. Walking into one opens it*/
+    pub open: bool,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Bystander { /*Harmless. The monster AI has it wander about instead of chasing the player*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+#[storage(FlaggedStorage)] /*Same as Position, for things starting or stopping blocking, like a door opening*/
+pub struct BlocksTile { /*Nothing else can stand on the same tile as an entity with this*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Size { /*Takes up width by height tiles, with its position as the top left corner. Anything without one takes up a single tile*/
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Size {
+    pub const SINGLE : Size = Size { width: 1, height: 1 };
+
+    pub fn tiles(&self, x: i32, y: i32) -> Vec<(i32, i32)> { /*Every tile covered with the corner at x, y*/
+        (y..y + self.height).flat_map(|tile_y| (x..x + self.width).map(move |tile_x| (tile_x, tile_y))).collect()
+    }
+
+    /*Whether something this size with its corner at a is next to, or on top of, something of the other size with its corner at b*/
+    pub fn touching(&self, a: rltk::Point, other: &Size, b: rltk::Point) -> bool {
+        let gap_x = i32::max(b.x - (a.x + self.width - 1), a.x - (b.x + other.width - 1));
+        let gap_y = i32::max(b.y - (a.y + self.height - 1), a.y - (b.y + other.height - 1));
+        gap_x <= 1 && gap_y <= 1
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Name { /*What to call an entity in messages*/
+    pub name: String,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Item { /*Marks an entity as something that can be picked up*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesHealing { /*Using this item restores heal_amount hp*/
+    pub heal_amount: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct InBackpack { /*The item is being carried by owner. Carried items have no Position, so they aren't drawn on the map*/
+    pub owner: Entity,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToPickupItem { /*Intent to pick something up, processed by the item collection system*/
+    pub collected_by: Entity,
+    pub item: Entity,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToUseItem { /*Intent to use an item from the backpack, processed by the item use system. target is the tile aimed at, for ranged items*/
+    pub item: Entity,
+    pub target: Option<rltk::Point>,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToDropItem { /*Intent to put an item from the backpack back on the floor*/
+    pub item: Entity,
+}
+
+/*Marker for entities that should be written to the save file. Every entity we create gets one*/
+pub struct SerializeMe;
+
+/*Marker for entities on a level that is being put away in the dungeon cache. Only used while the level is being written out or read back*/
+pub struct SerializeLevel;
+
+/*The map isn't an entity, so for saving it gets put inside one of these on a temporary entity.
+Anything added after the first save format needs a serde default, so older saves still load*/
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct SerializationHelper {
+    pub map: super::map::Map,
+    #[serde(default)]
+    pub stats: super::stats::Stats,
+    #[serde(default)]
+    pub dungeon: super::dungeon::MasterDungeon,
+    #[serde(default)]
+    pub identified: super::dungeon::IdentifiedItems,
+    #[serde(default)]
+    pub difficulty: super::difficulty::Difficulty, /*Older saves were all played on Normal*/
+    #[serde(default)]
+    pub rng: Option<rltk::RandomNumberGenerator>, /*Where the dice had got to, so a loaded game rolls the same as if it had never stopped. Older saves just keep rolling from wherever the current generator is*/
+    #[serde(default)]
+    pub name: super::stats::PlayerName,
+    #[serde(default)]
+    pub log: Vec<super::gamelog::LogEntry>, /*The newest messages, so the log carries on where it left off. Older saves start it empty*/
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ObfuscatedName { /*What an unidentified item is called until the player works out what it is*/
+    pub name: String,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Consumable { /*The item is used up when used*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Ranged { /*The item is aimed at a tile up to range tiles away instead of being used on yourself*/
+    pub range: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct InflictsDamage { /*Using this item hurts whatever it is aimed at*/
+    pub damage: i32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct AreaOfEffect { /*The item hits everything within radius of the target tile, not just the tile itself*/
+    pub radius: i32,
+}
+
+/*Anything that wears off after a number of turns. The HUD lists whichever of these the player is under, without knowing what each one does*/
+pub trait StatusEffect {
+    fn label(&self) -> &'static str;
+    fn colour(&self) -> RGB;
+    fn turns_left(&self) -> i32;
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Confusion { /*On an item, confuses whatever it hits for this many turns. On a monster, how many turns of confusion are left*/
+    pub turns: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Charmed { /*On an item, charms the monster it hits for this many turns. On a monster, how many turns it has left fighting on the player's side*/
+    pub turns: i32,
+}
+
+impl StatusEffect for Confusion {
+    fn label(&self) -> &'static str { "Confused" }
+    fn colour(&self) -> RGB { RGB::named(rltk::MAGENTA) }
+    fn turns_left(&self) -> i32 { self.turns }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct DamageOverTime { /*Poison working its way through. Hurts this much every turn until the turns run out*/
+    pub damage: i32,
+    pub turns: i32,
+}
+
+impl DamageOverTime {
+    /*Being poisoned again doesn't add up. The worse of the two doses and the longer of the two times win, so a second bite tops it back up*/
+    pub fn apply(store: &mut WriteStorage<DamageOverTime>, victim: Entity, damage: i32, turns: i32) {
+        if let Some(poison) = store.get_mut(victim) {
+            poison.damage = i32::max(poison.damage, damage);
+            poison.turns = i32::max(poison.turns, turns);
+        } else {
+            store.insert(victim, DamageOverTime { damage, turns }).expect("Unable to insert status");
+        }
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Poisonous { /*Hits that do damage also poison the target. Works on a weapon or on the attacker itself*/
+    pub damage: i32,
+    pub turns: i32,
+}
+
+impl StatusEffect for Charmed {
+    fn label(&self) -> &'static str { "Charmed" }
+    fn colour(&self) -> RGB { RGB::named(rltk::GOLD) }
+    fn turns_left(&self) -> i32 { self.turns }
+}
+
+impl StatusEffect for DamageOverTime {
+    fn label(&self) -> &'static str { "Poisoned" }
+    fn colour(&self) -> RGB { RGB::named(rltk::GREEN) }
+    fn turns_left(&self) -> i32 { self.turns }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum EquipmentSlot { Melee, Shield } /*Where an item is worn. Only one item can be in each slot*/
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Equippable { /*The item can be worn or wielded instead of used up*/
+    pub slot: EquipmentSlot,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Equipped { /*The item is being worn by owner. Like the backpack, it has no Position while it is*/
+    pub owner: Entity,
+    pub slot: EquipmentSlot,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MeleePowerBonus { /*Added to the wearer's power while equipped*/
+    pub power: i32,
+    #[serde(default)]
+    pub damage_type: DamageType, /*What kind of damage hitting with it does*/
+    #[serde(default = "melee_reach")]
+    pub range: i32, /*How far away it can hit from. Anything over 1 can be fired with the fire key, next door is just a normal bump*/
+}
+
+fn melee_reach() -> i32 { 1 } /*Weapons saved before ranges existed were all plain melee weapons*/
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Ammo { /*Shots left in a weapon that needs ammunition to fire. Hitting things up close doesn't use any*/
+    pub count: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Stackable { /*Carried items with this and the same name share one line in the inventory. Each one is still its own entity*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Charges { /*An item that can be used this many more times instead of being used up. Empty ones stay around until they are recharged*/
+    pub current: i32,
+    pub max: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct DefenseBonus { /*Added to the wearer's defense while equipped*/
+    pub defense: i32,
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum HungerState { WellFed, Normal, Hungry, Starving } /*Each one lasts a while before sliding into the next, until you eat*/
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct HungerClock { /*How hungry the player is, and how many turns are left before they get hungrier*/
+    pub state: HungerState,
+    pub duration: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Speed { /*Energy builds up every turn, and the entity acts each time it passes the threshold. A lower threshold is faster*/
+    pub energy: i32,
+    pub threshold: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct LightSource { /*Lights up the tiles around it, on the floor or carried*/
+    pub radius: i32,
+    pub color: RGB,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Knockback { /*Hits push the target this many tiles away. Works on a weapon or on the attacker itself*/
+    pub distance: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MagicMapper { /*Reading this item shows you the whole level*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesFood { /*Eating this item makes you well fed again*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Hidden { /*Not drawn and not in tooltips until something reveals it*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct EntryTrigger { /*Goes off when something steps onto its tile*/
+
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct SingleActivation { /*Deletes itself after going off once*/
+
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct ParticleLifetime { /*Particles are short lived effects that delete themselves once this runs out. They are never saved*/
+    pub lifetime_ms: f32,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct BlinkUntil { /*A newly spawned monster flickers for a moment the first time it is seen, so it doesn't just appear. Cosmetic, so never saved*/
+    pub ms_remaining: f32,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct FloatingText { /*A number that drifts up from whatever got hit and fades out. Like particles, these are never saved*/
+    pub text: String,
+    pub remaining_ms: f32,
+    pub dy_per_ms: f32, /*How fast it rises, in tiles per millisecond*/
+    pub offset_y: f32, /*How far it has risen so far*/
+    pub lifetime_ms: f32, /*What remaining_ms started at, so we know how faded it should be*/
+    pub fg: RGB,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rltk::Point;
+    use crate::map::{Map, TileType};
+
+    #[test]
+    fn visible_indices_drop_points_off_the_map_and_repeats() {
+        let (width, height) = (6, 5);
+        let map = Map { tiles: vec![TileType::Floor; (width * height) as usize], width, height, ..Default::default() };
+        for corner in [Point::new(0, 0), Point::new(width - 1, 0), Point::new(0, height - 1), Point::new(width - 1, height - 1)] {
+            let mut visible_tiles = rltk::field_of_view(corner, 3, &map);
+            visible_tiles.extend([corner, Point::new(-1, 0), Point::new(width, height - 1), Point::new(0, height)]); /*A repeat, and more points past each edge*/
+            let viewshed = Viewshed { visible_tiles, range: 3, dirty: false };
+
+            let indices : Vec<usize> = viewshed.visible_indices(&map).collect();
+            let mut expected : Vec<usize> = viewshed.visible_tiles.iter().filter(|p| map.in_bounds(p.x, p.y)).map(|p| map.xy_idx(p.x, p.y)).collect();
+            expected.sort();
+            expected.dedup();
+            let mut sorted = indices.clone();
+            sorted.sort();
+            assert_eq!(indices.len(), sorted.len());
+            assert_eq!(sorted, expected, "looking out from {},{}", corner.x, corner.y);
+            assert!(indices.contains(&map.xy_idx(corner.x, corner.y)));
+        }
+    }
+}
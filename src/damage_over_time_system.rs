@@ -0,0 +1,35 @@
+use specs::prelude::*;
+use super::{DamageOverTime, SufferDamage, DamageType, CombatStats, Name, gamelog::{GameLog, Logger}};
+
+pub struct DamageOverTimeSystem {}
+
+impl<'a> System<'a> for DamageOverTimeSystem { /*Hurts everything that is poisoned once a turn, through the damage queue like any other hit, and lets the poison wear off*/
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, DamageOverTime>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, Name>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut poisoned, combat_stats, mut inflict_damage, names, player_entity, mut log) = data;
+
+        let mut cured = Vec::new();
+        for (entity, poison, stats) in (&entities, &mut poisoned, &combat_stats).join() {
+            if stats.hp <= 0 { continue; } /*Already dead, it just hasn't been cleaned up yet*/
+            SufferDamage::new_damage(&mut inflict_damage, entity, poison.damage, DamageType::Physical);
+            poison.turns -= 1;
+            if poison.turns < 1 { cured.push(entity); }
+        }
+
+        for entity in cured {
+            poisoned.remove(entity);
+            if entity == *player_entity {
+                log.add("The poison wears off.");
+            } else if let Some(name) = names.get(entity) {
+                Logger::new().append("The").npc_name(name.name.to_lowercase()).append("is no longer poisoned").log(&mut log);
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use specs::prelude::*;
+use rltk::RGB;
+use super::{CombatStats, SufferDamage, Player, Name, Position, FloatingText, Map, gamelog::{GameLog, Logger}, stats::Stats, deletion_queue::ToDelete, camera::ScreenShake};
+
+pub struct DamageSystem {}
+
+const FLOATING_TEXT_MS : f32 = 600.0;
+
+/*While on, nothing hurts the player, whether it is a monster, a trap, lava or hunger. Only switched on from the debug console*/
+#[derive(Default)]
+pub struct GodMode(pub bool);
+
+impl<'a> System<'a> for DamageSystem { /*Applies all the damage that was queued up this turn*/
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, Name>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, Position>,
+                        WriteExpect<'a, Map>,
+                        WriteStorage<'a, FloatingText>,
+                        WriteExpect<'a, Stats>,
+                        ReadStorage<'a, Player>,
+                        WriteExpect<'a, ScreenShake>,
+                        ReadExpect<'a, GodMode>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut stats, mut damage, names, mut log, mut positions, mut map, mut floating_text, mut run_stats, players, mut shake, god_mode) = data;
+
+        for (entity, stats, damage) in (&entities, &mut stats, &damage).join() {
+            let was_alive = stats.hp > 0;
+            let protected = god_mode.0 && players.get(entity).is_some(); /*Nothing gets through, so nothing is said about being hurt either*/
+            let mut total = 0;
+            for (amount, damage_type) in damage.amount.iter() { /*Resistances and weaknesses change each hit on its own*/
+                let adjusted = stats.adjusted_damage(*amount, *damage_type);
+                if adjusted != *amount && !protected {
+                    let name = names.get(entity).map_or("Something", |n| n.name.as_str());
+                    let reaction = if adjusted < *amount { "resists the" } else { "is weak to" };
+                    Logger::new().npc_name(name).append(reaction).append(damage_type.name()).append("and takes").damage(adjusted).append("damage").log(&mut log);
+                }
+                total += adjusted;
+            }
+            if protected {
+                if total > 0 { Logger::new().append("Godmode blocks").damage(total).append("damage").log(&mut log); }
+                continue;
+            }
+            if total == 0 { continue; } /*Resisted down to nothing, so no blood, no number and no shake*/
+            stats.hp -= total;
+            if players.get(entity).is_some() { shake.hit(total, stats.max_hp); }
+            if let Some((x, y)) = positions.get(entity).map(|pos| (pos.x, pos.y)) { /*Leave a mark where the hit happened*/
+                let idx = map.xy_idx(x, y);
+                map.bloodstains.insert(idx);
+
+                let text = entities.create(); /*and a number that floats up out of it*/
+                positions.insert(text, Position { x, y: y - 1 }).expect("Unable to insert position");
+                floating_text.insert(text, FloatingText {
+                    text: format!("-{}", total),
+                    remaining_ms: FLOATING_TEXT_MS,
+                    dy_per_ms: 2.0 / FLOATING_TEXT_MS, /*Rises two tiles over its lifetime*/
+                    offset_y: 0.0,
+                    lifetime_ms: FLOATING_TEXT_MS,
+                    fg: RGB::named(rltk::RED),
+                }).expect("Unable to insert floating text");
+            }
+            if was_alive && stats.hp < 1 { /*Only announce the death once, on the hit that caused it*/
+                if players.get(entity).is_none() { run_stats.kills += 1; }
+                if let Some(name) = names.get(entity) {
+                    Logger::new().npc_name(&name.name).append("is dead").log(&mut log);
+                }
+            }
+        }
+
+        damage.clear();
+    }
+}
+
+pub struct DeadCleanupSystem {}
+
+impl<'a> System<'a> for DeadCleanupSystem { /*Queues up anything that has run out of hp to be deleted once the turn is over*/
+    type SystemData = ( Entities<'a>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Player>,
+                        WriteExpect<'a, ToDelete>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, combat_stats, players, mut to_delete) = data;
+
+        for (entity, stats) in (&entities, &combat_stats).join() {
+            if stats.hp < 1 {
+                match players.get(entity) {
+                    None => to_delete.push(entity),
+                    Some(_) => rltk::console::log("You are dead"), /*Don't delete the player, the game over screen needs them*/
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DamageType, map::TileType};
+
+    fn damage_world(god_mode: bool) -> World {
+        let mut ecs = World::new();
+        crate::saveload_system::register_components(&mut ecs);
+        ecs.insert(GameLog::new("Start"));
+        ecs.insert(Map { tiles: vec![TileType::Floor; 25], width: 5, height: 5, ..Default::default() });
+        ecs.insert(Stats::default());
+        ecs.insert(ScreenShake::new());
+        ecs.insert(GodMode(god_mode));
+        ecs
+    }
+
+    #[test]
+    fn godmode_says_nothing_about_hits_that_did_no_damage() {
+        let mut ecs = damage_world(true);
+        let player = ecs.create_entity().with(Player{})
+            .with(CombatStats { max_hp: 10, hp: 10, defense: 0, power: 1, resistances: vec![DamageType::Fire], weaknesses: Vec::new() })
+            .build();
+
+        SufferDamage::new_damage(&mut ecs.write_storage::<SufferDamage>(), player, 1, DamageType::Fire); /*Resisted down to nothing*/
+        DamageSystem{}.run_now(&ecs);
+        assert_eq!(ecs.fetch::<GameLog>().entries.len(), 1);
+
+        SufferDamage::new_damage(&mut ecs.write_storage::<SufferDamage>(), player, 6, DamageType::Fire);
+        DamageSystem{}.run_now(&ecs);
+        let log = ecs.fetch::<GameLog>();
+        assert_eq!(log.entries.len(), 2); /*Only the block, not the resistance as well*/
+        assert_eq!(log.entries[1].fragments.iter().map(|f| f.text.as_str()).collect::<Vec<_>>(), ["Godmode blocks", "3", "damage"]);
+        assert_eq!(ecs.read_storage::<CombatStats>().get(player).map(|stats| stats.hp), Some(10));
+    }
+
+    #[test]
+    fn a_hit_resisted_to_nothing_leaves_no_mark() {
+        let mut ecs = damage_world(false);
+        let player = ecs.create_entity().with(Player{}).with(Position { x: 2, y: 2 })
+            .with(CombatStats { max_hp: 4, hp: 4, defense: 0, power: 1, resistances: vec![DamageType::Fire], weaknesses: Vec::new() })
+            .build();
+
+        SufferDamage::new_damage(&mut ecs.write_storage::<SufferDamage>(), player, 1, DamageType::Fire); /*Halved and rounded down to 0*/
+        DamageSystem{}.run_now(&ecs);
+        ecs.maintain();
+        assert_eq!(ecs.read_storage::<CombatStats>().get(player).map(|stats| stats.hp), Some(4));
+        assert!(ecs.fetch::<Map>().bloodstains.is_empty());
+        assert_eq!(ecs.read_storage::<FloatingText>().join().count(), 0);
+        assert_eq!(ecs.fetch::<ScreenShake>().ms_remaining, 0.0);
+
+        SufferDamage::new_damage(&mut ecs.write_storage::<SufferDamage>(), player, 2, DamageType::Physical); /*A real hit still does all three*/
+        DamageSystem{}.run_now(&ecs);
+        ecs.maintain();
+        assert_eq!(ecs.read_storage::<CombatStats>().get(player).map(|stats| stats.hp), Some(2));
+        assert_eq!(ecs.fetch::<Map>().bloodstains.len(), 1);
+        assert_eq!(ecs.read_storage::<FloatingText>().join().map(|text| text.text.clone()).collect::<Vec<_>>(), ["-2"]);
+        assert!(ecs.fetch::<ScreenShake>().ms_remaining > 0.0);
+    }
+}
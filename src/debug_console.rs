@@ -0,0 +1,57 @@
+use specs::prelude::*;
+use super::{RunState, Map, CombatStats, Position, gamelog::GameLog, damage_system::GodMode, raws::{RawMaster, spawn_named_entity}};
+
+pub const MAX_COMMAND_LENGTH : usize = 30;
+
+/*Carries out one line typed into the debug console, straight on the world, and logs it. Only built with the debug feature.
+  heal           back to full health
+  reveal         the whole level is marked as seen
+  spawn <name>   anything from spawns.ron, next to the player
+  descend        down to the next level, stairs or not
+  godmode        nothing hurts the player until it is typed again*/
+pub fn run_command(ecs: &mut World, line: &str) -> RunState {
+    let line = line.trim();
+    if line.is_empty() { return RunState::Paused; }
+    ecs.write_resource::<GameLog>().add(format!("> {}", line));
+    let (command, argument) = line.split_once(' ').map_or((line, ""), |(command, argument)| (command, argument.trim()));
+
+    match command.to_lowercase().as_str() {
+        "heal" => {
+            let player_entity = *ecs.fetch::<Entity>();
+            if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(player_entity) { stats.hp = stats.max_hp; }
+        }
+        "reveal" => {
+            let mut map = ecs.write_resource::<Map>();
+            for revealed in map.revealed_tiles.iter_mut() { *revealed = true; }
+        }
+        "spawn" => spawn(ecs, argument),
+        "descend" => return RunState::NextLevel,
+        "godmode" => {
+            let mut god_mode = ecs.write_resource::<GodMode>();
+            god_mode.0 = !god_mode.0;
+            let state = if god_mode.0 { "on" } else { "off" };
+            ecs.write_resource::<GameLog>().add(format!("Godmode is {}.", state));
+        }
+        _ => ecs.write_resource::<GameLog>().add(format!("Unknown command {}. Try heal, reveal, spawn <name>, descend or godmode.", command)),
+    }
+    RunState::Paused
+}
+
+fn spawn(ecs: &mut World, name: &str) {
+    let raws = RawMaster::clone(&ecs.fetch::<RawMaster>());
+    let name = match raws.find_name(name) {
+        Some(name) => name,
+        None => {
+            ecs.write_resource::<GameLog>().add(format!("Nothing called {} in spawns.ron.", name));
+            return;
+        }
+    };
+    let spot = { /*The first open tile next to the player, or on top of them if they are boxed in*/
+        let map = ecs.fetch::<Map>();
+        let player_pos = *ecs.fetch::<rltk::Point>();
+        (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (player_pos.x + dx, player_pos.y + dy)))
+            .find(|(x, y)| map.in_bounds(*x, *y) && !map.blocked[map.xy_idx(*x, *y)])
+            .unwrap_or((player_pos.x, player_pos.y))
+    };
+    spawn_named_entity(&raws, ecs, name, Position { x: spot.0, y: spot.1 });
+}
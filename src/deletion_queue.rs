@@ -0,0 +1,34 @@
+use specs::prelude::*;
+use super::{Position, Name, CombatStats, InBackpack, Renderable};
+
+/*Entities that systems are done with, like the dead and used up items. Systems push onto this instead of deleting while they still
+have storages borrowed, and run_systems deletes the lot once every system has finished*/
+#[derive(Default)]
+pub struct ToDelete {
+    pub entities: Vec<Entity>,
+}
+
+impl ToDelete {
+    pub fn push(&mut self, entity: Entity) {
+        self.entities.push(entity);
+    }
+}
+
+fn still_stored<T : Component>(ecs: &World, entity: Entity) -> bool { /*Checks the storage itself, since get() already hides dead entities*/
+    ecs.read_storage::<T>().mask().contains(entity.id())
+}
+
+pub fn delete_queued(ecs: &mut World) {
+    let mut doomed = std::mem::take(&mut ecs.write_resource::<ToDelete>().entities);
+    doomed.sort();
+    doomed.dedup(); /*Something can be queued twice, like an item used up in the same turn its user died*/
+    doomed.retain(|entity| ecs.is_alive(*entity));
+    for entity in doomed.iter() {
+        ecs.delete_entity(*entity).expect("Unable to delete");
+    }
+
+    debug_assert!(ecs.fetch::<ToDelete>().entities.is_empty());
+    debug_assert!(doomed.iter().all(|entity| !ecs.is_alive(*entity)
+        && !still_stored::<Position>(ecs, *entity) && !still_stored::<Name>(ecs, *entity) && !still_stored::<CombatStats>(ecs, *entity)
+        && !still_stored::<InBackpack>(ecs, *entity) && !still_stored::<Renderable>(ecs, *entity)));
+}
@@ -0,0 +1,112 @@
+use serde::{Serialize, Deserialize};
+
+/*How hard the current run is. Picked on the main menu before a new game and saved with it, since everything spawned
+after the start still needs to know*/
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum Difficulty { Easy, #[default] Normal, Hard }
+
+impl Difficulty {
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn next(self) -> Difficulty { /*The menu steps through them in order, and wraps back round*/
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    fn monster_hp_percent(self) -> i32 {
+        match self { Difficulty::Easy => 75, Difficulty::Normal => 100, Difficulty::Hard => 150 }
+    }
+
+    fn monster_power_percent(self) -> i32 {
+        match self { Difficulty::Easy => 75, Difficulty::Normal => 100, Difficulty::Hard => 125 }
+    }
+
+    fn player_hp_percent(self) -> i32 {
+        match self { Difficulty::Easy => 150, Difficulty::Normal => 100, Difficulty::Hard => 80 }
+    }
+
+    pub fn extra_spawns(self) -> i32 { /*Added to the number of things rolled for each room*/
+        match self { Difficulty::Easy => -1, Difficulty::Normal => 0, Difficulty::Hard => 1 }
+    }
+
+    pub fn monster_hp(self, hp: i32) -> i32 { scale(hp, self.monster_hp_percent()) }
+    pub fn monster_power(self, power: i32) -> i32 { scale(power, self.monster_power_percent()) }
+    pub fn player_hp(self, hp: i32) -> i32 { scale(hp, self.player_hp_percent()) }
+}
+
+fn scale(value: i32, percent: i32) -> i32 { /*Rounded to the nearest whole number. Something that started out above zero is never scaled all the way down to nothing*/
+    let scaled = (value * percent + 50) / 100;
+    if value > 0 { i32::max(1, scaled) } else { scaled }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use rltk::RandomNumberGenerator;
+    use specs::prelude::*;
+    use specs::saveload::SimpleMarkerAllocator;
+    use crate::{CombatStats, Monster, Name, SerializeMe, spawner, map::new_map, raws::RawMaster, saveload_system::register_components, dungeon::{MasterDungeon, IdentifiedItems}};
+
+    #[test]
+    fn monster_hp_scales_with_difficulty() {
+        assert_eq!([Difficulty::Easy, Difficulty::Normal, Difficulty::Hard].map(|d| d.monster_hp(16)), [12, 16, 24]);
+    }
+
+    fn spawned_hp(difficulty: Difficulty) -> HashMap<String, i32> { /*The max hp of each kind of monster spawned into a level from the same seed*/
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(difficulty);
+        ecs.insert(RawMaster::built_in());
+        ecs.insert(MasterDungeon::default());
+        ecs.insert(IdentifiedItems::default());
+        let mut rng = RandomNumberGenerator::seeded(5);
+        let (map, rooms) = new_map(&mut rng, 4, 80, 50);
+        ecs.insert(rng);
+        ecs.insert(map);
+        for room in rooms.iter() {
+            spawner::spawn_room(&mut ecs, room);
+        }
+        let (names, stats, monsters) = (ecs.read_storage::<Name>(), ecs.read_storage::<CombatStats>(), ecs.read_storage::<Monster>());
+        (&names, &stats, &monsters).join().map(|(name, stats, _monster)| (name.name.clone(), stats.max_hp)).collect()
+    }
+
+    #[test]
+    fn hard_monsters_are_tougher_than_easy_ones() {
+        let (easy, hard) = (spawned_hp(Difficulty::Easy), spawned_hp(Difficulty::Hard));
+        let both : Vec<&String> = easy.keys().filter(|name| hard.contains_key(*name)).collect();
+        assert!(!both.is_empty(), "no kind of monster turned up on both levels");
+        for name in both {
+            assert!(hard[name] > easy[name], "a {} has {} hp on hard and {} on easy", name, hard[name], easy[name]);
+        }
+    }
+
+    #[test]
+    fn monster_power_scales_with_difficulty() {
+        assert_eq!([Difficulty::Easy, Difficulty::Normal, Difficulty::Hard].map(|d| d.monster_power(4)), [3, 4, 5]);
+    }
+
+    #[test]
+    fn the_player_gets_more_hp_on_easy() {
+        assert_eq!([Difficulty::Easy, Difficulty::Normal, Difficulty::Hard].map(|d| d.player_hp(30)), [45, 30, 24]);
+    }
+
+    #[test]
+    fn scaling_rounds_to_nearest_and_only_reaches_zero_from_zero() {
+        assert_eq!(scale(3, 75), 2); /*2.25*/
+        assert_eq!(scale(2, 75), 2); /*1.5 rounds up*/
+        assert_eq!(scale(1, 75), 1);
+        assert_eq!(scale(1, 10), 1); /*0.1 would round to nothing*/
+        assert_eq!(scale(0, 150), 0); /*Nothing stays nothing*/
+    }
+}
@@ -0,0 +1,128 @@
+use rltk::{RGB, Rltk, Point};
+use specs::prelude::*;
+use super::{Map, TileType, Position, Renderable, FloatingText, Monster, Hidden, CombatStats, DamageOverTime, BlinkUntil, Size, camera::Viewport, game_time::GameTime};
+
+const RIPPLE_STEPS : u64 = 5; /*How many animation steps water and lava hold each frame of their ripple*/
+const BLINK_STEPS : u64 = 2; /*How many animation steps a blinking monster stays shown, and then hidden*/
+
+pub const MAX_LIGHT_DISTANCE : f32 = 8.0; /*How far the player's own glow reaches, the same as their sight. Tiles out at this distance are as dim as they get*/
+const EDGE_DIMMING : f32 = 0.6; /*How far toward the ambient colour a tile at the edge goes. All the way would make it darker than the greyed out tiles past it*/
+const AMBIENT : RGB = RGB { r: 0.05, g: 0.05, b: 0.15 }; /*The dark that the glow fades into*/
+
+/*Draws every tile the player has seen that is inside the viewport. Tiles out of view right now are greyed out*/
+pub fn draw_map(ecs: &World, ctx: &mut Rltk, viewport: &Viewport) {
+    let map = ecs.fetch::<Map>();
+    let ripple_step = ecs.fetch::<GameTime>().total_steps() / RIPPLE_STEPS;
+    let player_pos = *ecs.fetch::<Point>();
+    for y in viewport.min_y..viewport.max_y {
+        for x in viewport.min_x..viewport.max_x {
+            if !map.in_bounds(x, y) { continue; } /*A map smaller than the screen leaves some of it empty*/
+            let idx = map.xy_idx(x, y);
+            if !map.revealed_tiles[idx] { continue; } /*Only draw tiles the player has actually seen*/
+
+            let ripple = (ripple_step + (x + y) as u64).is_multiple_of(2); /*Offset by position, so the waves roll across the pool instead of blinking*/
+            let (glyph, mut fg, mut bg) = match map.tiles[idx] { /*Liquids ripple the same at every depth, everything else is up to the level's theme*/
+                TileType::ShallowWater => (rltk::to_cp437(if ripple { '~' } else { '-' }), RGB::from_f32(0.3, 0.7, 1.0), RGB::from_f32(0., 0., 0.)),
+                TileType::DeepWater => (
+                    rltk::to_cp437(if ripple { '≈' } else { '~' }),
+                    if ripple { RGB::from_f32(0.1, 0.3, 1.0) } else { RGB::from_f32(0.0, 0.2, 0.8) },
+                    RGB::from_f32(0.0, 0.0, 0.25),
+                ),
+                TileType::Lava => (
+                    rltk::to_cp437(if ripple { '≈' } else { '~' }),
+                    if ripple { RGB::from_f32(1.0, 0.5, 0.0) } else { RGB::from_f32(1.0, 0.2, 0.0) },
+                    RGB::from_f32(0.3, 0.0, 0.0),
+                ),
+                _ => map.theme.tile_glyph(idx, &map),
+            };
+            if map.visible_tiles[idx] && map.is_lit(idx) { /*Lit tiles pick up the colour of the light, and glow a little*/
+                let light = map.light[idx];
+                let strength = f32::min(1.0, (light.r + light.g + light.b) / 3.0);
+                fg = fg.lerp(light, 0.5 * strength);
+                bg = light * 0.15;
+            } else if map.visible_tiles[idx] { /*Otherwise the player's glow fades off the further the tile is from them*/
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(player_pos, Point::new(x, y));
+                fg = fg.lerp(AMBIENT, f32::min(1.0, distance / MAX_LIGHT_DISTANCE) * EDGE_DIMMING);
+            }
+            if map.bloodstains.contains(&idx) { bg = RGB::from_f32(0.5, 0.0, 0.0); } /*Something got hurt here*/
+            if !map.visible_tiles[idx] { fg = fg.to_greyscale() } /*Seen before but not in view right now, so draw it greyed out*/
+            if let Some((screen_x, screen_y)) = viewport.to_screen(x, y) {
+                ctx.set(screen_x, screen_y, fg, bg, glyph);
+            }
+        }
+    }
+}
+
+const THREAT_MARGIN : i32 = 1; /*How close a monster's strength has to be to the player's to count as an even fight*/
+
+/*A faint background showing how a monster measures up to the player. Strength is just power plus defense, which is rough but easy to read*/
+fn threat_colour(monster: &CombatStats, player: &CombatStats) -> RGB {
+    let difference = (monster.power + monster.defense) - (player.power + player.defense);
+    if difference < -THREAT_MARGIN {
+        RGB::from_f32(0.0, 0.25, 0.0) /*Weaker*/
+    } else if difference > THREAT_MARGIN {
+        RGB::from_f32(0.35, 0.0, 0.0) /*Stronger*/
+    } else {
+        RGB::from_f32(0.3, 0.3, 0.0) /*About the same*/
+    }
+}
+
+/*Draws everything the player can see that has a position and something to draw. Call it after draw_map so entities end up on top.
+With show_threat on, monsters get a background colour based on how dangerous they are*/
+pub fn draw_entities(ecs: &World, ctx: &mut Rltk, viewport: &Viewport, show_threat: bool) {
+    let positions = ecs.read_storage::<Position>(); /*Gain read only access from the ECS to the container used to store position components*/
+    let renderables = ecs.read_storage::<Renderable>(); /*Same for renderables*/
+    let monsters = ecs.read_storage::<Monster>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let poisoned = ecs.read_storage::<DamageOverTime>();
+    let blinking = ecs.read_storage::<BlinkUntil>();
+    let sizes = ecs.read_storage::<Size>();
+    let blink_hidden = (ecs.fetch::<GameTime>().total_steps() / BLINK_STEPS) % 2 == 1;
+    let entities = ecs.entities();
+    let map = ecs.fetch::<Map>();
+    let player_stats = combat_stats.get(*ecs.fetch::<Entity>());
+
+    let mut data = (&entities, &positions, &renderables, !&hidden).join()
+        .map(|(entity, pos, render, ())| {
+            let tiles = sizes.get(entity).unwrap_or(&Size::SINGLE).tiles(pos.x, pos.y).into_iter() /*Something big is drawn over every tile it covers*/
+                .filter(|(x, y)| map.in_bounds(*x, *y) && map.visible_tiles[map.xy_idx(*x, *y)]) /*Things out of sight, or not found yet, aren't drawn*/
+                .collect::<Vec<_>>();
+            (entity, tiles, render)
+        })
+        .filter(|(_entity, tiles, _render)| !tiles.is_empty())
+        .collect::<Vec<_>>();
+    data.sort_by_key(|(_entity, _tiles, render)| std::cmp::Reverse(render.render_order)); /*Highest render order first, so the player is drawn last and stays on top of anything sharing their tile*/
+    for (entity, tiles, render) in data.iter() {
+        if blink_hidden && blinking.get(*entity).is_some() { continue; } /*Off half the time while it blinks, so whatever it is standing on shows through*/
+        let mut bg = render.bg;
+        if show_threat && monsters.get(*entity).is_some() {
+            if let (Some(stats), Some(player)) = (combat_stats.get(*entity), player_stats) {
+                bg = threat_colour(stats, player);
+            }
+        }
+        let fg = if poisoned.get(*entity).is_some() { render.fg.lerp(RGB::named(rltk::GREEN), 0.6) } else { render.fg }; /*Poison shows as a sickly green*/
+        for (x, y) in tiles.iter() {
+            if let Some((screen_x, screen_y)) = viewport.to_screen(*x, *y) { /*Only what the camera is looking at*/
+                ctx.set(screen_x, screen_y, fg, bg, render.glyph); /*Sets these properties of relevant entities to be rendered on the screen*/
+            }
+        }
+    }
+}
+
+/*Draws the damage numbers rising off anything that got hit. Call it after draw_entities so the numbers go over the top*/
+pub fn draw_floating_text(ecs: &World, ctx: &mut Rltk, viewport: &Viewport) {
+    let positions = ecs.read_storage::<Position>();
+    let texts = ecs.read_storage::<FloatingText>();
+    let map = ecs.fetch::<Map>();
+
+    for (pos, text) in (&positions, &texts).join() {
+        let y = pos.y - text.offset_y as i32;
+        if !map.in_bounds(pos.x, y) || !map.visible_tiles[map.xy_idx(pos.x, y)] { continue; }
+        let fade = (text.remaining_ms / text.lifetime_ms).clamp(0.0, 1.0); /*1 when it appears, 0 when it's gone*/
+        let fg = RGB::from_f32(text.fg.r * fade, text.fg.g * fade, text.fg.b * fade);
+        if let Some((screen_x, screen_y)) = viewport.to_screen(pos.x, y) {
+            ctx.print_color(screen_x, screen_y, fg, RGB::named(rltk::BLACK), &text.text);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use rltk::RandomNumberGenerator;
+use super::{Map, Name, ObfuscatedName};
+
+const POTION_ADJECTIVES : [&str; 6] = ["fizzy", "murky", "glowing", "cloudy", "bubbling", "oily"];
+const POTION_COLOURS : [&str; 6] = ["blue", "red", "green", "amber", "violet", "black"];
+const SCROLL_SYLLABLES : [&str; 10] = ["ka", "zoo", "lund", "ix", "mor", "tep", "vo", "ra", "nil", "qua"];
+
+/*A level the player has left. The entities are kept as the same json the save file uses, so they can be written back into the world later*/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedLevel {
+    pub map: Map,
+    pub entities: String,
+}
+
+/*Every level the player has visited but isn't on right now, by depth. Saved along with the map so going back up still works after loading*/
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct MasterDungeon {
+    levels: HashMap<i32, CachedLevel>,
+    #[serde(default)]
+    item_names: HashMap<String, String>, /*Real item name to the made up one it goes by until it is identified. Rolled fresh every run*/
+}
+
+impl MasterDungeon {
+    pub fn store_level(&mut self, map: Map, entities: String) {
+        self.levels.insert(map.depth, CachedLevel { map, entities });
+    }
+
+    pub fn take_level(&mut self, depth: i32) -> Option<CachedLevel> { /*Removed from the cache, since it becomes the current level*/
+        self.levels.remove(&depth)
+    }
+
+    /*Gives every potion and scroll a made up name, so "fizzy blue potion" means something different each run. No two kinds share a name*/
+    pub fn obfuscate_names(&mut self, rng: &mut RandomNumberGenerator, potions: &[&str], scrolls: &[&str]) {
+        let mut taken = HashSet::new();
+        for potion in potions.iter() {
+            if taken.len() >= POTION_ADJECTIVES.len() * POTION_COLOURS.len() { break; } /*Ran out of names, so the rest go by their real ones*/
+            let name = loop {
+                let name = format!("{} {} potion", POTION_ADJECTIVES[rng.range(0, POTION_ADJECTIVES.len())], POTION_COLOURS[rng.range(0, POTION_COLOURS.len())]);
+                if taken.insert(name.clone()) { break name; }
+            };
+            self.item_names.insert(potion.to_string(), name);
+        }
+        for scroll in scrolls.iter() {
+            let name = loop {
+                let word : String = (0..rng.range(2, 4)).map(|_| SCROLL_SYLLABLES[rng.range(0, SCROLL_SYLLABLES.len())]).collect();
+                let name = format!("scroll titled {}", word.to_uppercase());
+                if taken.insert(name.clone()) { break name; }
+            };
+            self.item_names.insert(scroll.to_string(), name);
+        }
+    }
+
+    pub fn obfuscated_name(&self, real_name: &str) -> Option<&String> {
+        self.item_names.get(real_name)
+    }
+}
+
+/*Which kinds of item the player knows, by their real name. Once a kind is known every one of them shows its real name*/
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct IdentifiedItems {
+    names: HashSet<String>,
+}
+
+impl IdentifiedItems {
+    pub fn identify(&mut self, real_name: &str) {
+        self.names.insert(real_name.to_string());
+    }
+
+    pub fn display_name<'a>(&self, name: &'a Name, obfuscated: Option<&'a ObfuscatedName>) -> &'a str { /*What the player should see an item called*/
+        match obfuscated {
+            Some(obfuscated) if !self.names.contains(&name.name) => &obfuscated.name,
+            _ => &name.name,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use specs::prelude::*;
+use super::Speed;
+
+/*How much energy everything gets for each turn the player takes. A threshold of this much is normal speed,
+half of it acts twice a turn and double it acts every other turn*/
+pub const TURN_ENERGY : i32 = 100;
+pub const NORMAL_THRESHOLD : i32 = TURN_ENERGY;
+pub const MAX_ACTIONS_PER_TURN : usize = 4; /*However fast something is, the player still gets a go now and then*/
+
+pub struct EnergySystem {}
+
+impl<'a> System<'a> for EnergySystem { /*The player did something, so the rest of the world gets a turn's worth of energy*/
+    type SystemData = WriteStorage<'a, Speed>;
+
+    fn run(&mut self, mut speeds : Self::SystemData) {
+        for speed in (&mut speeds).join() {
+            speed.energy += TURN_ENERGY;
+        }
+    }
+}
+
+/*Whether an entity gets to act this time round, spending the energy if so. Things without a Speed act once a turn, in the first round*/
+pub fn try_act(speed: Option<&mut Speed>, round: usize) -> bool {
+    match speed {
+        None => round == 0,
+        Some(speed) if speed.energy >= speed.threshold => {
+            speed.energy -= speed.threshold;
+            true
+        }
+        Some(_) => false,
+    }
+}
+
+pub fn anyone_ready(ecs: &World) -> bool { /*Is there anything with enough energy left for another action this turn?*/
+    ecs.read_storage::<Speed>().join().any(|speed| speed.energy >= speed.threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_speed_acts_twice_a_turn() {
+        let mut ecs = World::new();
+        ecs.register::<Speed>();
+        let normal = ecs.create_entity().with(Speed { energy: 0, threshold: NORMAL_THRESHOLD }).build();
+        let fast = ecs.create_entity().with(Speed { energy: 0, threshold: NORMAL_THRESHOLD / 2 }).build();
+        let slow = ecs.create_entity().with(Speed { energy: 0, threshold: NORMAL_THRESHOLD * 2 }).build();
+
+        let turns = 10;
+        let mut actions = [0, 0, 0];
+        for _turn in 0..turns { /*The same rounds run_systems goes through*/
+            EnergySystem{}.run_now(&ecs);
+            for round in 0..MAX_ACTIONS_PER_TURN {
+                if round > 0 && !anyone_ready(&ecs) { break; }
+                let mut speeds = ecs.write_storage::<Speed>();
+                for (count, entity) in actions.iter_mut().zip([normal, fast, slow]) {
+                    if try_act(speeds.get_mut(entity), round) { *count += 1; }
+                }
+            }
+        }
+        assert_eq!(actions, [turns, turns * 2, turns / 2]);
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+/*The sides every creature is on. Anything without a Faction goes by these too, so saves from before factions still behave*/
+pub const PLAYER_FACTION : &str = "Player";
+pub const MONSTER_FACTION : &str = "Monsters";
+
+/*What a creature does about another one it can see*/
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Reaction { Attack, Ignore, Flee }
+
+/*How each faction feels about each other one, looked up by (whoever is deciding, whoever they see). It never changes during a run,
+so it isn't saved. Anything not in the table is ignored*/
+pub struct FactionTable {
+    reactions : HashMap<(String, String), Reaction>,
+}
+
+impl Default for FactionTable {
+    fn default() -> FactionTable { /*Monsters against the player, the way it has always been*/
+        let mut table = FactionTable { reactions: HashMap::new() };
+        table.set(MONSTER_FACTION, PLAYER_FACTION, Reaction::Attack);
+        table.set(PLAYER_FACTION, MONSTER_FACTION, Reaction::Attack);
+        table
+    }
+}
+
+impl FactionTable {
+    pub fn set(&mut self, mine: &str, theirs: &str, reaction: Reaction) {
+        self.reactions.insert((mine.to_string(), theirs.to_string()), reaction);
+    }
+
+    pub fn reaction(&self, mine: &str, theirs: &str) -> Reaction {
+        self.reactions.get(&(mine.to_string(), theirs.to_string())).copied().unwrap_or(Reaction::Ignore)
+    }
+}
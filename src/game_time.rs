@@ -0,0 +1,55 @@
+/*Real time, cut up into fixed size animation steps. Anything that animates moves on by whole steps,
+so it looks the same however fast the screen is redrawing*/
+pub const ANIMATION_STEP_MS : f32 = 100.0;
+
+#[derive(Default)]
+pub struct GameTime {
+    pub accumulated_ms: f32, /*Time that hasn't made up a whole step yet*/
+    steps_this_frame: i32,
+    total_steps: u64,
+}
+
+impl GameTime {
+    /*Called once a frame with how long the frame took. Whole steps are used up and the rest carries over to the next frame*/
+    pub fn advance(&mut self, frame_time_ms: f32) {
+        self.accumulated_ms += frame_time_ms;
+        self.steps_this_frame = (self.accumulated_ms / ANIMATION_STEP_MS) as i32;
+        self.accumulated_ms -= self.steps_this_frame as f32 * ANIMATION_STEP_MS;
+        self.total_steps += self.steps_this_frame as u64;
+    }
+
+    pub fn animation_steps(&self) -> i32 { /*How many steps the last advance used up, often none*/
+        self.steps_this_frame
+    }
+
+    pub fn total_steps(&self) -> u64 { /*Every step since the game started, for things that loop forever like rippling water*/
+        self.total_steps
+    }
+
+    pub fn animation_ms(&self) -> f32 { /*The same, in milliseconds, for things that count down in ms*/
+        self.animation_steps() as f32 * ANIMATION_STEP_MS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_steps_are_used_and_the_rest_carries_over() {
+        let mut time = GameTime::default();
+        time.advance(250.0);
+        assert_eq!(time.animation_steps(), 2);
+        assert_eq!(time.accumulated_ms, 50.0);
+        assert_eq!(time.animation_ms(), 200.0);
+
+        time.advance(30.0); /*Not enough for another step yet*/
+        assert_eq!(time.animation_steps(), 0);
+        assert_eq!(time.accumulated_ms, 80.0);
+
+        time.advance(20.0); /*With what carried over, that makes a whole step*/
+        assert_eq!(time.animation_steps(), 1);
+        assert_eq!(time.accumulated_ms, 0.0);
+        assert_eq!(time.total_steps(), 3);
+    }
+}
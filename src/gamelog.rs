@@ -0,0 +1,71 @@
+use rltk::RGB;
+use std::collections::VecDeque;
+use serde::{Serialize, Deserialize};
+
+pub const MAX_ENTRIES : usize = 1000; /*Older messages than this fall off the front, so a long game doesn't keep every message it ever logged*/
+pub const SAVED_ENTRIES : usize = 100; /*How many of the newest messages go in a save file*/
+
+/*One piece of a log message, all in the same colour*/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LogFragment {
+    pub colour: RGB,
+    pub text: String,
+}
+
+/*A whole message. It is drawn fragment by fragment with a space between each one*/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LogEntry {
+    pub fragments: Vec<LogFragment>,
+}
+
+/*The message log shown at the bottom of the screen. Systems add entries to it, newest last*/
+pub struct GameLog {
+    pub entries: VecDeque<LogEntry>,
+}
+
+impl GameLog {
+    pub fn new<S: ToString>(first_message: S) -> GameLog {
+        let mut log = GameLog { entries: VecDeque::new() };
+        log.add(first_message);
+        log
+    }
+
+    pub fn add<S: ToString>(&mut self, text: S) { /*A plain message, all in white*/
+        Logger::new().append(text).log(self);
+    }
+
+    pub fn newest(&self, count: usize) -> Vec<LogEntry> { /*The last count messages, oldest first, for saving*/
+        self.entries.iter().skip(self.entries.len().saturating_sub(count)).cloned().collect()
+    }
+}
+
+/*Builds a coloured log entry a piece at a time, like Logger::new().npc_name("Goblin").append("hits you for").damage(5).log(&mut gamelog)*/
+pub struct Logger {
+    fragments: Vec<LogFragment>,
+}
+
+impl Logger {
+    pub fn new() -> Logger {
+        Logger { fragments: Vec::new() }
+    }
+
+    pub fn append<S: ToString>(self, text: S) -> Logger { /*Ordinary text, in white*/
+        self.fragment(text, RGB::named(rltk::WHITE))
+    }
+
+    /*The bits worth picking out at a glance, each kind in its own colour*/
+    pub fn npc_name<S: ToString>(self, name: S) -> Logger { self.fragment(name, RGB::named(rltk::YELLOW)) }
+    pub fn item_name<S: ToString>(self, name: S) -> Logger { self.fragment(name, RGB::named(rltk::CYAN)) }
+    pub fn damage(self, amount: i32) -> Logger { self.fragment(amount, RGB::named(rltk::RED)) }
+    pub fn healing(self, amount: i32) -> Logger { self.fragment(amount, RGB::named(rltk::GREEN)) }
+
+    fn fragment<S: ToString>(mut self, text: S, colour: RGB) -> Logger {
+        self.fragments.push(LogFragment { colour, text: text.to_string() });
+        self
+    }
+
+    pub fn log(self, gamelog: &mut GameLog) {
+        gamelog.entries.push_back(LogEntry { fragments: self.fragments });
+        if gamelog.entries.len() > MAX_ENTRIES { gamelog.entries.pop_front(); }
+    }
+}
@@ -0,0 +1,731 @@
+use rltk::{RGB, Rltk, VirtualKeyCode};
+use specs::prelude::*;
+use super::{CombatStats, Hidden, Monster, HungerClock, HungerState, Player, Name, InBackpack, Viewshed, AreaOfEffect, Map, TileType, camera, gamelog::GameLog, stats::{Stats, PlayerName}, saveload_system::SlotSummary,
+    ObfuscatedName, Position, dungeon::IdentifiedItems, keybindings::{Action, Keybindings}, DamageType, ProvidesHealing, InflictsDamage, Confusion, Charmed, Ranged, MeleePowerBonus, DefenseBonus, Ammo, Charges, Stackable,
+    ProvidesFood, MagicMapper, LightSource, Knockback, StatusEffect, DamageOverTime, Poisonous, difficulty::Difficulty, inventory_system, hotbar::{Hotbar, HOTBAR_SLOTS}};
+use rltk::Point;
+
+pub const PANEL_HEIGHT: i32 = 7; /*How many rows at the bottom of the screen belong to the UI panel. The map gets whatever is left*/
+
+/*Draws the panel at the bottom of the screen: the player's health and the most recent log messages*/
+pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
+    let (screen_width, screen_height) = ctx.get_char_size();
+    let (screen_width, screen_height) = (screen_width as i32, screen_height as i32);
+    let top = screen_height - PANEL_HEIGHT;
+    ctx.draw_box(0, top, screen_width - 1, PANEL_HEIGHT - 1, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let players = ecs.read_storage::<Player>();
+    for (_player, stats) in (&players, &combat_stats).join() { /*The health label and bar sit on the top edge of the box*/
+        let health = format!(" HP: {}/{} ", stats.hp, stats.max_hp);
+        ctx.print_color(12, top, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &health);
+        ctx.draw_bar_horizontal(28, top, screen_width - 29, stats.hp, stats.max_hp, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+    }
+
+    let hunger = ecs.read_storage::<HungerClock>(); /*How hungry the player is goes on the top edge too, left of the health. Normal isn't worth mentioning*/
+    for (_player, clock) in (&players, &hunger).join() {
+        match clock.state {
+            HungerState::WellFed => ctx.print_color(2, top, RGB::named(rltk::GREEN), RGB::named(rltk::BLACK), " Well Fed "),
+            HungerState::Normal => {}
+            HungerState::Hungry => ctx.print_color(2, top, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), " Hungry "),
+            HungerState::Starving => ctx.print_color(2, top, RGB::named(rltk::RED), RGB::named(rltk::BLACK), " Starving "),
+        }
+    }
+
+    let player_entity = ecs.fetch::<Entity>(); /*Whatever the player is under goes along the bottom edge, each in its own colour*/
+    let mut x = 2;
+    for (label, colour) in status_effects(ecs, *player_entity) {
+        let label = format!(" {} ", label);
+        ctx.print_color(x, screen_height - 1, colour, RGB::named(rltk::BLACK), &label);
+        x += label.chars().count() as i32;
+    }
+
+    let monsters = ecs.read_storage::<Monster>(); /*Who is playing, how far down and how many are left to deal with, in the bottom right corner*/
+    let living = (&monsters, &combat_stats).join().filter(|(_monster, stats)| stats.hp > 0).count();
+    let progress = format!(" {}  Depth: {}  Monsters: {} ", ecs.fetch::<PlayerName>().name, ecs.fetch::<Map>().depth, living);
+    ctx.print_color(screen_width - 2 - progress.chars().count() as i32, screen_height - 1, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &progress);
+
+    let log = ecs.fetch::<GameLog>(); /*The newest messages go at the top of the panel*/
+    for (y, entry) in (top + 1..screen_height - 1).zip(log.entries.iter().rev()) {
+        let mut x = 2;
+        for fragment in entry.fragments.iter() { /*Each piece in its own colour, with a space after it*/
+            ctx.print_color(x, y, fragment.colour, RGB::named(rltk::BLACK), &fragment.text);
+            x += fragment.text.chars().count() as i32 + 1;
+        }
+    }
+
+    draw_hotbar(ecs, ctx, screen_width - HOTBAR_WIDTH - 1, top + 1);
+
+    draw_tooltips(ecs, ctx);
+}
+
+const HOTBAR_WIDTH : i32 = 24;
+
+/*The quickslots down the right of the panel, one a row, over the end of any long log message*/
+fn draw_hotbar(ecs: &World, ctx: &mut Rltk, x: i32, y: i32) {
+    let hotbar = ecs.fetch::<Hotbar>();
+    let names = ecs.read_storage::<Name>();
+    let obfuscated = ecs.read_storage::<ObfuscatedName>();
+    let identified = ecs.fetch::<IdentifiedItems>();
+    for slot in 1..=HOTBAR_SLOTS {
+        let row = y + slot as i32 - 1;
+        for i in 0..HOTBAR_WIDTH { ctx.set(x + i, row, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(' ')); }
+        ctx.print_color(x + 1, row, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), slot.to_string());
+        match hotbar.item(ecs, slot).and_then(|item| names.get(item).map(|name| (item, name))) {
+            Some((item, name)) => {
+                let label : String = identified.display_name(name, obfuscated.get(item)).chars().take(HOTBAR_WIDTH as usize - 4).collect();
+                ctx.print_color(x + 3, row, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), label);
+            }
+            None => ctx.print_color(x + 3, row, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), "-"),
+        }
+    }
+}
+
+/*Every status effect the entity is under, with the turns it has left. New effects only need adding to the list*/
+fn status_effects(ecs: &World, entity: Entity) -> Vec<(String, RGB)> {
+    fn add<T: Component + StatusEffect>(ecs: &World, entity: Entity, statuses: &mut Vec<(String, RGB)>) {
+        if let Some(effect) = ecs.read_storage::<T>().get(entity) {
+            statuses.push((format!("{} ({})", effect.label(), effect.turns_left()), effect.colour()));
+        }
+    }
+    let mut statuses = Vec::new();
+    add::<Confusion>(ecs, entity, &mut statuses);
+    add::<Charmed>(ecs, entity, &mut statuses);
+    add::<DamageOverTime>(ecs, entity, &mut statuses);
+    statuses
+}
+
+const MINIMAP_WIDTH : i32 = 20;
+const MINIMAP_HEIGHT : i32 = 15;
+
+/*A shrunk down copy of the revealed map in the top right corner. Every character is a half block, so it holds two pixels stacked on top of each other,
+and each pixel stands in for a little block of map tiles. The player is the bright dot*/
+pub fn draw_minimap(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<Point>();
+    let (screen_width, _screen_height) = ctx.get_char_size();
+    let left = screen_width as i32 - MINIMAP_WIDTH;
+    let tiles_wide = (map.width + MINIMAP_WIDTH - 1) / MINIMAP_WIDTH; /*Rounded up so the whole map fits*/
+    let tiles_high = (map.height + MINIMAP_HEIGHT * 2 - 1) / (MINIMAP_HEIGHT * 2);
+
+    let pixel = |px: i32, py: i32| -> RGB { /*The colour of one pixel, from the most interesting tile in its block*/
+        let (min_x, min_y) = (px * tiles_wide, py * tiles_high);
+        if (min_x..min_x + tiles_wide).contains(&player_pos.x) && (min_y..min_y + tiles_high).contains(&player_pos.y) {
+            return RGB::named(rltk::YELLOW);
+        }
+        let mut colour = RGB::named(rltk::BLACK); /*Nothing seen here yet*/
+        for y in min_y..i32::min(min_y + tiles_high, map.height) {
+            for x in min_x..i32::min(min_x + tiles_wide, map.width) {
+                let idx = map.xy_idx(x, y);
+                if !map.revealed_tiles[idx] { continue; }
+                match map.tiles[idx] {
+                    TileType::DownStairs | TileType::UpStairs => return RGB::named(rltk::CYAN),
+                    TileType::Floor => colour = RGB::from_f32(0.0, 0.4, 0.4),
+                    TileType::Wall => if colour == RGB::named(rltk::BLACK) { colour = RGB::from_f32(0.0, 0.6, 0.0) },
+                    TileType::ShallowWater | TileType::DeepWater => colour = RGB::from_f32(0.0, 0.3, 0.8),
+                    TileType::Lava => colour = RGB::from_f32(0.8, 0.2, 0.0),
+                }
+            }
+        }
+        colour
+    };
+
+    for y in 0..MINIMAP_HEIGHT {
+        for x in 0..MINIMAP_WIDTH {
+            ctx.set(left + x, y, pixel(x, y * 2), pixel(x, y * 2 + 1), 223); /*The top half block, so the foreground is the upper pixel*/
+        }
+    }
+}
+
+/*One line for each named thing on a tile, as the tooltips and examine mode show them. Hidden things are left out, or they would give traps away.
+With details the line also has a monster's stats, or what an item does once the player knows what it is*/
+fn describe_entities(ecs: &World, idx: usize, details: bool) -> Vec<String> {
+    let map = ecs.fetch::<Map>();
+    let names = ecs.read_storage::<Name>();
+    let obfuscated = ecs.read_storage::<ObfuscatedName>();
+    let identified = ecs.fetch::<IdentifiedItems>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let monsters = ecs.read_storage::<Monster>();
+    let charmed = ecs.read_storage::<Charmed>();
+
+    map.tile_content[idx].iter()
+        .filter(|entity| hidden.get(**entity).is_none())
+        .filter_map(|entity| names.get(*entity).map(|name| (*entity, name)))
+        .map(|(entity, name)| {
+            let line = match monsters.get(entity) {
+                Some(_monster) if charmed.get(entity).is_some() => format!("{} (charmed)", name.name),
+                Some(monster) if monster.fleeing => format!("{} (fleeing)", name.name),
+                _ => identified.display_name(name, obfuscated.get(entity)).to_string(),
+            };
+            let unidentified = obfuscated.get(entity).is_some() && identified.display_name(name, obfuscated.get(entity)) != name.name;
+            match entity_details(ecs, entity) {
+                Some(_) if details && unidentified => format!("{}: not yet identified", line), /*Examining it isn't a free way to find out*/
+                Some(detail) if details => format!("{}: {}", line, detail),
+                _ => line,
+            }
+        })
+        .collect()
+}
+
+/*The numbers behind an entity, for examine mode. None if there's nothing worth saying*/
+fn entity_details(ecs: &World, entity: Entity) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(stats) = ecs.read_storage::<CombatStats>().get(entity) {
+        parts.push(format!("HP {}/{}, power {}, defense {}", stats.hp, stats.max_hp, stats.power, stats.defense));
+        for resistance in stats.resistances.iter() { parts.push(format!("resists {}", resistance.name())); }
+        for weakness in stats.weaknesses.iter() { parts.push(format!("weak to {}", weakness.name())); }
+    }
+    if let Some(healing) = ecs.read_storage::<ProvidesHealing>().get(entity) { parts.push(format!("heals {}", healing.heal_amount)); }
+    if let Some(damage) = ecs.read_storage::<InflictsDamage>().get(entity) {
+        match damage.damage_type {
+            DamageType::Physical => parts.push(format!("{} damage", damage.damage)),
+            damage_type => parts.push(format!("{} {} damage", damage.damage, damage_type.name())),
+        }
+    }
+    if let Some(area) = ecs.read_storage::<AreaOfEffect>().get(entity) { parts.push(format!("radius {}", area.radius)); }
+    if let Some(confusion) = ecs.read_storage::<Confusion>().get(entity) { parts.push(format!("confuses for {} turns", confusion.turns)); }
+    if let Some(charm) = ecs.read_storage::<Charmed>().get(entity) { parts.push(format!("charms for {} turns", charm.turns)); }
+    if let Some(ranged) = ecs.read_storage::<Ranged>().get(entity) { parts.push(format!("range {}", ranged.range)); }
+    if let Some(bonus) = ecs.read_storage::<MeleePowerBonus>().get(entity) {
+        match bonus.damage_type {
+            DamageType::Physical => parts.push(format!("+{} power", bonus.power)),
+            damage_type => parts.push(format!("+{} power, {} damage", bonus.power, damage_type.name())),
+        }
+        if bonus.range > 1 { parts.push(format!("reach {}", bonus.range)); }
+    }
+    if let Some(ammo) = ecs.read_storage::<Ammo>().get(entity) { parts.push(format!("{} shots left", ammo.count)); }
+    if let Some(charges) = ecs.read_storage::<Charges>().get(entity) { parts.push(format!("{} of {} charges", charges.current, charges.max)); }
+    if let Some(bonus) = ecs.read_storage::<DefenseBonus>().get(entity) { parts.push(format!("+{} defense", bonus.defense)); }
+    if let Some(knockback) = ecs.read_storage::<Knockback>().get(entity) { parts.push(format!("knocks back {}", knockback.distance)); }
+    if let Some(poison) = ecs.read_storage::<Poisonous>().get(entity) { parts.push(format!("poisons for {} turns", poison.turns)); }
+    if let Some(poison) = ecs.read_storage::<DamageOverTime>().get(entity) { parts.push(format!("poisoned for {} more turns", poison.turns)); }
+    if ecs.read_storage::<ProvidesFood>().get(entity).is_some() { parts.push("food".to_string()); }
+    if ecs.read_storage::<MagicMapper>().get(entity).is_some() { parts.push("maps the level".to_string()); }
+    if let Some(light) = ecs.read_storage::<LightSource>().get(entity) { parts.push(format!("lights {} tiles around it", light.radius)); }
+    if parts.is_empty() { None } else { Some(parts.join(", ")) }
+}
+
+/*Shows the names of whatever is under the mouse, as long as the player can currently see that tile*/
+fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+
+    let mouse_pos = ctx.mouse_pos();
+    let mouse_tile = match camera::get_screen_bounds(ecs, ctx).to_world(mouse_pos.0, mouse_pos.1) { /*The tile under the mouse, through the camera*/
+        Some(tile) => tile,
+        None => return,
+    };
+    if !map.in_bounds(mouse_tile.x, mouse_tile.y) { return; }
+    let idx = map.xy_idx(mouse_tile.x, mouse_tile.y);
+    if !map.revealed_tiles[idx] || !map.visible_tiles[idx] { return; } /*No peeking at things out of sight*/
+
+    let tooltip = describe_entities(ecs, idx, false);
+    if tooltip.is_empty() { return; }
+
+    let width = tooltip.iter().map(|s| s.len() as i32).max().unwrap_or(0) + 3;
+    let fg = RGB::named(rltk::WHITE);
+    let bg = RGB::named(rltk::GREY);
+    if mouse_pos.0 > ctx.get_char_size().0 as i32 / 2 { /*Put the box on whichever side of the cursor has room*/
+        let left_x = mouse_pos.0 - width;
+        for (y, line) in (mouse_pos.1..).zip(tooltip.iter()) {
+            ctx.print_color(left_x, y, fg, bg, line);
+            for i in 0..width - 1 - line.len() as i32 { /*Pad the lines out so the box has a straight edge*/
+                ctx.print_color(left_x + line.len() as i32 + i, y, fg, bg, " ");
+            }
+            ctx.print_color(mouse_pos.0 - 1, y, fg, bg, "-");
+        }
+    } else {
+        let left_x = mouse_pos.0 + 2;
+        for (y, line) in (mouse_pos.1..).zip(tooltip.iter()) {
+            ctx.print_color(mouse_pos.0 + 1, y, fg, bg, "-");
+            ctx.print_color(left_x, y, fg, bg, line);
+            for i in 0..width - 1 - line.len() as i32 {
+                ctx.print_color(left_x + line.len() as i32 + i, y, fg, bg, " ");
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum ItemMenuResult { Cancel, NoResponse, Selected }
+
+/*The menu shown when pressing i*/
+pub fn show_inventory(ecs: &World, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    item_menu(ecs, ctx, "Inventory", |_item| true)
+}
+
+/*The menu shown when pressing d. Same list as the inventory, only the title changes*/
+pub fn drop_item_menu(ecs: &World, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    item_menu(ecs, ctx, "Drop Which Item?", |_item| true)
+}
+
+/*The menu shown when pressing v. Only things that do something to whatever they land on are listed*/
+pub fn throw_item_menu(ecs: &World, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    item_menu(ecs, ctx, "Throw Which Item?", |item| inventory_system::is_throwable(ecs, item))
+}
+
+/*The menu shown when pressing a. Whatever is picked goes on a number key next*/
+pub fn quickslot_item_menu(ecs: &World, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    item_menu(ecs, ctx, "Quickslot Which Item?", |_item| true)
+}
+
+/*Asks which number key the item goes on. Returns the slot once one is pressed*/
+pub fn quickslot_prompt(ecs: &World, ctx: &mut Rltk, item: Entity) -> (ItemMenuResult, Option<u8>) {
+    let name = match ecs.read_storage::<Name>().get(item) {
+        Some(name) => ecs.fetch::<IdentifiedItems>().display_name(name, ecs.read_storage::<ObfuscatedName>().get(item)).to_string(),
+        None => return (ItemMenuResult::Cancel, None), /*Gone from under the menu, nothing to assign*/
+    };
+    let prompt = format!("Put the {} on which key? (1-{})", name, HOTBAR_SLOTS);
+    let (screen_width, screen_height) = ctx.get_char_size();
+    let width = prompt.chars().count() as i32 + 4;
+    let (left, top) = (screen_width as i32 / 2 - width / 2, screen_height as i32 / 2 - 2);
+    ctx.draw_box(left, top, width, 4, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(left + 2, top + 2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &prompt);
+    ctx.print_color_centered(top + 4, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), "ESCAPE to cancel");
+
+    let slot = match ctx.key {
+        None => return (ItemMenuResult::NoResponse, None),
+        Some(VirtualKeyCode::Escape) => return (ItemMenuResult::Cancel, None),
+        Some(VirtualKeyCode::Key1) => 1,
+        Some(VirtualKeyCode::Key2) => 2,
+        Some(VirtualKeyCode::Key3) => 3,
+        Some(VirtualKeyCode::Key4) => 4,
+        Some(VirtualKeyCode::Key5) => 5,
+        Some(_) => return (ItemMenuResult::NoResponse, None),
+    };
+    (ItemMenuResult::Selected, Some(slot))
+}
+
+/*Draws the player's backpack as a lettered list and waits for them to pick something. Returns the picked item once they do.
+Items the filter turns down are left off the list. Stackable items with the same name share a line, and picking it picks one of them*/
+fn item_menu(ecs: &World, ctx: &mut Rltk, title: &str, filter: impl Fn(Entity) -> bool) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = ecs.fetch::<Entity>();
+    let names = ecs.read_storage::<Name>();
+    let obfuscated = ecs.read_storage::<ObfuscatedName>();
+    let identified = ecs.fetch::<IdentifiedItems>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let charges = ecs.read_storage::<Charges>();
+    let stackable = ecs.read_storage::<Stackable>();
+    let entities = ecs.entities();
+
+    let mut stacks : Vec<(Entity, Option<&str>, i32)> = Vec::new(); /*The item the line stands for, the name it stacks under if any, and how many there are*/
+    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity && filter(item.0)) {
+        let key = stackable.get(entity).map(|_| name.name.as_str()); /*Grouped by real name, so two unknown potions never share a line*/
+        match stacks.iter_mut().find(|stack| key.is_some() && stack.1 == key) {
+            Some(stack) => stack.2 += 1,
+            None => stacks.push((entity, key, 1)),
+        }
+    }
+    let inventory : Vec<(Entity, String)> = stacks.into_iter()
+        .map(|(entity, _key, count)| {
+            let name = identified.display_name(names.get(entity).unwrap(), obfuscated.get(entity));
+            match charges.get(entity) { /*Wands show how many uses they have left*/
+                Some(charges) => (entity, format!("{} ({}/{})", name, charges.current, charges.max)),
+                None if count > 1 => (entity, format!("{} x{}", name, count)),
+                None => (entity, name.to_string()),
+            }
+        })
+        .collect();
+    let count = inventory.len() as i32;
+
+    let top = ctx.get_char_size().1 as i32 / 2 - (count / 2); /*Keep the list centred on the screen*/
+    ctx.draw_box(15, top - 2, 31, count + 3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, top - 2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+    ctx.print_color(18, top + count + 1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
+
+    for (j, (_entity, name)) in inventory.iter().enumerate() { /*Each item gets a letter, starting at a*/
+        let y = top + j as i32;
+        ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97 + j as rltk::FontCharType);
+        ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+
+        ctx.print(21, y, name);
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count {
+                    return (ItemMenuResult::Selected, Some(inventory[selection as usize].0));
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuSelection { SaveSlot(u8), Difficulty, NewGame, SaveGame, LoadGame, Quit }
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuResult { NoSelection { selected: MainMenuSelection }, Selected { selected: MainMenuSelection }, DeleteSlot { slot: u8 }, Cancel }
+
+/*The menu shown at startup, on death and when pressing escape. Up and down move the highlight, enter picks it.
+The save slots are listed first, and picking one makes it the slot that Save Game and Load Game use. Shift+Delete on a slot asks to wipe it.
+Picking the difficulty steps it on to the next one, for the next New Game.
+Save Game is only offered while a game is being played, and Load Game is greyed out when the chosen slot is empty*/
+pub fn main_menu(selection: MainMenuSelection, in_game: bool, slots: &[Option<SlotSummary>], active_slot: u8, difficulty: Difficulty, message: Option<&str>, ctx: &mut Rltk) -> MainMenuResult {
+    let mut options = Vec::new();
+    for (slot, summary) in (0..).zip(slots.iter()) {
+        let marker = if slot == active_slot { ">" } else { " " }; /*Which slot saving and loading will use*/
+        let label = match summary {
+            Some(summary) => format!("{} Slot {}: Depth {}, turn {}", marker, slot + 1, summary.depth, summary.turns),
+            None => format!("{} Slot {}: Empty", marker, slot + 1),
+        };
+        options.push((MainMenuSelection::SaveSlot(slot), label, true));
+    }
+    options.push((MainMenuSelection::Difficulty, format!("Difficulty: {}", difficulty.name()), true));
+    options.push((MainMenuSelection::NewGame, "New Game".to_string(), true));
+    if in_game { options.push((MainMenuSelection::SaveGame, "Save Game".to_string(), true)); }
+    options.push((MainMenuSelection::LoadGame, "Load Game".to_string(), slots[active_slot as usize].is_some()));
+    options.push((MainMenuSelection::Quit, "Quit".to_string(), true));
+
+    let (screen_width, screen_height) = ctx.get_char_size();
+    let (left, top) = (screen_width as i32 / 2 - 16, screen_height as i32 / 2 - 7); /*Roughly in the middle whatever the window size*/
+    ctx.draw_box(left, top, 31, options.len() as i32 + 3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color_centered(top, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "KazooGame");
+    for (y, (option, label, enabled)) in (top + 2..).zip(options.iter()) {
+        let fg = if !*enabled {
+            RGB::named(rltk::GRAY) /*Can't be picked*/
+        } else if *option == selection {
+            RGB::named(rltk::MAGENTA) /*The highlighted option stands out*/
+        } else {
+            RGB::named(rltk::WHITE)
+        };
+        ctx.print_color_centered(y, fg, RGB::named(rltk::BLACK), label);
+    }
+    if let Some(message) = message { /*Just below the box*/
+        ctx.print_color_centered(top + options.len() as i32 + 5, RGB::named(rltk::RED), RGB::named(rltk::BLACK), message);
+    }
+
+    let current = options.iter().position(|(option, _, enabled)| *option == selection && *enabled).unwrap_or(0); /*Fall back to the top if the selection isn't on offer*/
+    let step = |forward: bool| { /*Move to the next option that can be picked, wrapping around at the top and bottom*/
+        let mut idx = current;
+        loop {
+            idx = if forward { (idx + 1) % options.len() } else { (idx + options.len() - 1) % options.len() };
+            if options[idx].2 { return options[idx].0; }
+        }
+    };
+
+    match ctx.key {
+        None => MainMenuResult::NoSelection { selected: options[current].0 },
+        Some(key) => match (key, options[current].0) {
+            (VirtualKeyCode::Escape, _) => MainMenuResult::Cancel,
+            (VirtualKeyCode::Up, _) => MainMenuResult::NoSelection { selected: step(false) },
+            (VirtualKeyCode::Down, _) => MainMenuResult::NoSelection { selected: step(true) },
+            (VirtualKeyCode::Return, selected) => MainMenuResult::Selected { selected },
+            (VirtualKeyCode::Delete, MainMenuSelection::SaveSlot(slot)) if ctx.shift && slots[slot as usize].is_some() => MainMenuResult::DeleteSlot { slot },
+            (_, selected) => MainMenuResult::NoSelection { selected },
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum ConfirmResult { NoResponse, Yes, No }
+
+/*A yes or no question in a box in the middle of the screen, for anything that can't be taken back. Y answers yes, N or escape answers no*/
+pub fn confirm(prompt: &str, ctx: &mut Rltk) -> ConfirmResult {
+    let (screen_width, screen_height) = ctx.get_char_size();
+    let width = i32::max(prompt.chars().count() as i32, 12) + 4; /*Never narrower than the answer line*/
+    let (left, top) = (screen_width as i32 / 2 - width / 2, screen_height as i32 / 2 - 3);
+    ctx.draw_box(left, top, width, 4, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+    ctx.print_color_centered(top + 2, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), prompt);
+    ctx.print_color_centered(top + 3, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "(Y)es / (N)o");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Y) => ConfirmResult::Yes,
+        Some(VirtualKeyCode::N) | Some(VirtualKeyCode::Escape) => ConfirmResult::No,
+        _ => ConfirmResult::NoResponse,
+    }
+}
+
+/*Every tile the player can see that is close enough to aim at*/
+fn targetable_tiles(ecs: &World, range: i32) -> Vec<Point> {
+    let player_entity = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    match viewsheds.get(*player_entity) {
+        Some(visible) => visible.visible_tiles.iter()
+            .filter(|idx| rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, **idx) <= range as f32)
+            .copied()
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/*Monsters that can be aimed at, nearest first. Tab steps through these*/
+fn targetable_monsters(ecs: &World, available_cells: &[Point]) -> Vec<Point> {
+    let player_pos = ecs.fetch::<Point>();
+    let monsters = ecs.read_storage::<Monster>();
+    let positions = ecs.read_storage::<Position>();
+    let mut targets : Vec<Point> = (&monsters, &positions).join()
+        .map(|(_monster, pos)| Point::new(pos.x, pos.y))
+        .filter(|tile| available_cells.contains(tile))
+        .collect();
+    targets.sort_by(|a, b| rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *a)
+        .partial_cmp(&rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *b)).unwrap());
+    targets
+}
+
+/*Where the keyboard cursor starts when aiming: on the nearest monster in range, or on the player if there isn't one*/
+pub fn initial_target(ecs: &World, range: i32) -> Point {
+    let available_cells = targetable_tiles(ecs, range);
+    targetable_monsters(ecs, &available_cells).first().copied().unwrap_or(*ecs.fetch::<Point>())
+}
+
+/*Lets the player pick a tile up to range tiles away, with either the mouse or the keyboard. Tiles that can be targeted are highlighted.
+The mouse picks by clicking. The keyboard has a cursor that the movement keys push around, Tab jumps it to the next monster and Enter fires.
+For area of effect items the whole blast around the target is highlighted too*/
+pub fn ranged_target(ecs: &World, ctx: &mut Rltk, range: i32, item: Entity, cursor: &mut Point) -> (ItemMenuResult, Option<Point>) {
+    let viewport = camera::get_screen_bounds(ecs, ctx); /*Everything here is worked out in map tiles and drawn through the camera*/
+    let highlight = |ctx: &mut Rltk, tile: &Point, colour: RGB| {
+        if let Some((screen_x, screen_y)) = viewport.to_screen(tile.x, tile.y) {
+            ctx.set_bg(screen_x, screen_y, colour);
+        }
+    };
+
+    if ecs.read_storage::<Viewshed>().get(*ecs.fetch::<Entity>()).is_none() { return (ItemMenuResult::Cancel, None); }
+    let available_cells = targetable_tiles(ecs, range); /*Anything the player can see that is close enough*/
+    for tile in available_cells.iter() {
+        highlight(ctx, tile, RGB::named(rltk::BLUE));
+    }
+    let is_valid = |tile: &Point| available_cells.contains(tile);
+
+    let mouse_pos = ctx.mouse_pos();
+    let mouse_tile = match viewport.to_world(mouse_pos.0, mouse_pos.1) {
+        Some(tile) => tile,
+        None => Point::new(-1, -1), /*Over the UI panel, which is never a valid target*/
+    };
+    let target = if is_valid(&mouse_tile) { mouse_tile } else { *cursor }; /*The mouse wins while it is over something it could hit*/
+    if is_valid(&target) {
+        match ecs.read_storage::<AreaOfEffect>().get(item) {
+            None => {}
+            Some(area_effect) => { /*Show everything the blast would reach*/
+                let map = ecs.fetch::<Map>();
+                let mut blast_tiles = rltk::field_of_view(target, area_effect.radius, &*map);
+                blast_tiles.retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
+                for tile in blast_tiles.iter() {
+                    highlight(ctx, tile, RGB::named(rltk::ORANGE));
+                }
+            }
+        }
+        highlight(ctx, &target, RGB::named(rltk::CYAN));
+    }
+    if !is_valid(&mouse_tile) {
+        ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::RED)); /*Out of range, clicking here does nothing*/
+    }
+    highlight(ctx, cursor, RGB::named(rltk::MAGENTA)); /*The keyboard cursor always stands out, even when the mouse is aiming somewhere else*/
+
+    let title = { /*Name whatever is under the target, so you know what you're about to hit*/
+        let map = ecs.fetch::<Map>();
+        let names = ecs.read_storage::<Name>();
+        let hidden = ecs.read_storage::<Hidden>();
+        let target_name = if map.in_bounds(target.x, target.y) {
+            map.tile_content[map.xy_idx(target.x, target.y)].iter()
+                .filter(|entity| hidden.get(**entity).is_none())
+                .find_map(|entity| names.get(*entity))
+                .map(|name| name.name.clone())
+        } else { None };
+        match target_name {
+            Some(name) => format!("Select Target: {}", name),
+            None => "Select Target:".to_string(),
+        }
+    };
+    ctx.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+
+    if ctx.left_click && is_valid(&mouse_tile) {
+        return (ItemMenuResult::Selected, Some(mouse_tile));
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, None),
+        Some(VirtualKeyCode::Return) if is_valid(cursor) => (ItemMenuResult::Selected, Some(*cursor)),
+        Some(VirtualKeyCode::Tab) => {
+            let targets = targetable_monsters(ecs, &available_cells);
+            let next = match targets.iter().position(|tile| tile == cursor) {
+                Some(current) => targets.get(current + 1).or(targets.first()),
+                None => targets.first(),
+            };
+            if let Some(next) = next { *cursor = *next; }
+            (ItemMenuResult::NoResponse, None)
+        }
+        Some(key) => {
+            let direction = ecs.fetch::<Keybindings>().action_for(key).and_then(|action| action.direction());
+            if let Some((dx, dy)) = direction { /*Step over tiles that can't be aimed at, to the next one that can*/
+                let mut tile = *cursor;
+                for _ in 0..range * 2 {
+                    tile = Point::new(tile.x + dx, tile.y + dy);
+                    if is_valid(&tile) { *cursor = tile; break; }
+                }
+            }
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum GameOverResult { NoSelection, ShowLog, QuitToMenu }
+
+/*Shown once the player dies. Sums up how the run went and waits for any key. The log key opens the message log instead*/
+pub fn game_over(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
+    let stats = ecs.fetch::<Stats>();
+    let depth = ecs.fetch::<Map>().depth;
+
+    let top = ctx.get_char_size().1 as i32 / 2 - 10;
+    ctx.print_color_centered(top, RGB::named(rltk::RED), RGB::named(rltk::BLACK), format!("{} has died", ecs.fetch::<PlayerName>().name));
+    ctx.print_color_centered(top + 2, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("You reached depth {}", depth));
+    ctx.print_color_centered(top + 3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("You survived for {} turns", stats.turns));
+    ctx.print_color_centered(top + 4, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("You killed {} monsters", stats.kills));
+    ctx.print_color_centered(top + 6, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Press any key to return to the menu");
+    ctx.print_color_centered(top + 7, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), "or the log key to see what happened");
+
+    match ctx.key {
+        None => GameOverResult::NoSelection,
+        Some(key) if ecs.fetch::<Keybindings>().action_for(key) == Some(Action::ShowLog) => GameOverResult::ShowLog,
+        Some(_) => GameOverResult::QuitToMenu,
+    }
+}
+
+/*A one line prompt for typing something in. Letters, digits and spaces go into the buffer, capitals with shift held, up to max_length of them.
+Backspace rubs out the last one, enter accepts what is there and escape backs out. Whoever asked decides what an empty answer means*/
+pub fn text_entry(ctx: &mut Rltk, title: &str, buffer: &mut String, max_length: usize) -> ItemMenuResult {
+    let (screen_width, screen_height) = ctx.get_char_size();
+    let width = i32::max(title.len() as i32, max_length as i32) + 4;
+    let (left, top) = (screen_width as i32 / 2 - width / 2, screen_height as i32 / 2 - 2);
+    ctx.draw_box(left, top, width, 4, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(left + 2, top, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+    ctx.print_color(left + 2, top + 2, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("{}_", buffer)); /*The underscore shows where the next letter goes*/
+    ctx.print_color_centered(top + 4, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), "ENTER to accept, ESCAPE to go back");
+
+    match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::NumpadEnter) => ItemMenuResult::Selected,
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        Some(VirtualKeyCode::Back) => { buffer.pop(); ItemMenuResult::NoResponse }
+        Some(key) => {
+            if let Some(c) = typed_char(key, ctx.shift) {
+                if buffer.chars().count() < max_length { buffer.push(c); }
+            }
+            ItemMenuResult::NoResponse
+        }
+    }
+}
+
+fn typed_char(key: VirtualKeyCode, shift: bool) -> Option<char> { /*Only letters, spaces and the digits along the top of the keyboard*/
+    let letter = rltk::letter_to_option(key);
+    if letter >= 0 {
+        let c = (b'a' + letter as u8) as char;
+        return Some(if shift { c.to_ascii_uppercase() } else { c });
+    }
+    match key {
+        VirtualKeyCode::Space => Some(' '),
+        VirtualKeyCode::Key0 => Some('0'),
+        VirtualKeyCode::Key1 => Some('1'),
+        VirtualKeyCode::Key2 => Some('2'),
+        VirtualKeyCode::Key3 => Some('3'),
+        VirtualKeyCode::Key4 => Some('4'),
+        VirtualKeyCode::Key5 => Some('5'),
+        VirtualKeyCode::Key6 => Some('6'),
+        VirtualKeyCode::Key7 => Some('7'),
+        VirtualKeyCode::Key8 => Some('8'),
+        VirtualKeyCode::Key9 => Some('9'),
+        _ => None,
+    }
+}
+
+/*The whole message log on one screen, oldest at the top and newest at the bottom. scroll is how many messages back from the newest the bottom line is.
+Up and down move a line at a time, page up and page down a screenful, escape closes it*/
+pub fn show_log(ecs: &World, ctx: &mut Rltk, scroll: &mut usize) -> ItemMenuResult {
+    let (screen_width, screen_height) = ctx.get_char_size();
+    let (screen_width, screen_height) = (screen_width as i32, screen_height as i32);
+    ctx.draw_box(0, 0, screen_width - 1, screen_height - 1, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(2, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), " Message Log ");
+    ctx.print_color(2, screen_height - 1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), " Up/Down, PgUp/PgDn to scroll, Escape to close ");
+
+    let log = ecs.fetch::<GameLog>();
+    let page = (screen_height - 2) as usize; /*Lines inside the box*/
+    let max_scroll = log.entries.len().saturating_sub(page);
+    *scroll = usize::min(*scroll, max_scroll);
+    let end = log.entries.len() - *scroll;
+    let start = end.saturating_sub(page);
+    for (y, entry) in (1..).zip(log.entries.range(start..end)) {
+        let mut x = 2;
+        for fragment in entry.fragments.iter() {
+            ctx.print_color(x, y, fragment.colour, RGB::named(rltk::BLACK), &fragment.text);
+            x += fragment.text.chars().count() as i32 + 1;
+        }
+    }
+
+    match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Up => *scroll = usize::min(*scroll + 1, max_scroll), /*Up goes back in time*/
+                VirtualKeyCode::Down => *scroll = scroll.saturating_sub(1),
+                VirtualKeyCode::PageUp => *scroll = usize::min(*scroll + page, max_scroll),
+                VirtualKeyCode::PageDown => *scroll = scroll.saturating_sub(page),
+                _ => {}
+            }
+            ItemMenuResult::NoResponse
+        }
+    }
+}
+
+/*Looking around without the mouse. The movement keys push a cursor over the revealed tiles on screen and the top of the screen says what is there.
+What is standing on a tile is only listed while the player can see it, same as the tooltips. Escape, or the examine key again, stops looking*/
+pub fn examine(ecs: &World, ctx: &mut Rltk, cursor: &mut Point) -> ItemMenuResult {
+    let viewport = camera::get_screen_bounds(ecs, ctx);
+    if let Some((screen_x, screen_y)) = viewport.to_screen(cursor.x, cursor.y) {
+        ctx.set_bg(screen_x, screen_y, RGB::named(rltk::MAGENTA));
+    }
+
+    let (title, lines) = {
+        let map = ecs.fetch::<Map>();
+        if !map.in_bounds(cursor.x, cursor.y) || !map.revealed_tiles[map.xy_idx(cursor.x, cursor.y)] {
+            ("Unexplored".to_string(), Vec::new())
+        } else {
+            let idx = map.xy_idx(cursor.x, cursor.y);
+            let tile = match map.tiles[idx] {
+                TileType::Wall => "A wall",
+                TileType::Floor => "Floor",
+                TileType::DownStairs => "Stairs going down",
+                TileType::UpStairs => "Stairs going up",
+                TileType::ShallowWater => "Shallow water, slow to wade through",
+                TileType::DeepWater => "Deep water",
+                TileType::Lava => "Lava",
+            };
+            if map.visible_tiles[idx] {
+                (tile.to_string(), describe_entities(ecs, idx, true))
+            } else {
+                (format!("{} (out of sight)", tile), Vec::new())
+            }
+        }
+    };
+    ctx.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("Examine: {}", title));
+    for (y, line) in (1..).zip(lines.iter()) {
+        ctx.print_color(5, y, RGB::named(rltk::WHITE), RGB::named(rltk::GREY), line);
+    }
+
+    match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        Some(key) => {
+            let action = ecs.fetch::<Keybindings>().action_for(key);
+            if action == Some(Action::Examine) { return ItemMenuResult::Cancel; }
+            if let Some((dx, dy)) = action.and_then(|action| action.direction()) { /*Skip over unexplored tiles to the next one there is something to say about*/
+                let map = ecs.fetch::<Map>();
+                let mut tile = *cursor;
+                loop {
+                    tile = Point::new(tile.x + dx, tile.y + dy);
+                    if !map.in_bounds(tile.x, tile.y) || viewport.to_screen(tile.x, tile.y).is_none() { break; } /*Stop at the edge of the screen*/
+                    if map.revealed_tiles[map.xy_idx(tile.x, tile.y)] { *cursor = tile; break; }
+                }
+            }
+            ItemMenuResult::NoResponse
+        }
+    }
+}
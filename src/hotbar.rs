@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use specs::prelude::*;
+use super::{InBackpack, Name, Stackable};
+
+pub const HOTBAR_SLOTS : u8 = 5; /*Slots 1 to 5, on the number keys*/
+
+/*Items put on the number keys, so they can be used without opening the inventory. A slot only points at an item, so one that has since
+been used up or dropped just shows as empty. A slot holding something stackable moves on to the next of the stack instead.
+Not saved, since a loaded game gives every entity a new id*/
+#[derive(Default)]
+pub struct Hotbar {
+    slots : HashMap<u8, Entity>,
+    stacks : HashMap<u8, String>, /*The name to look for when a slot's item is gone, for slots holding something stackable*/
+}
+
+impl Hotbar {
+    pub fn assign(&mut self, ecs: &World, slot: u8, item: Entity) { /*An item or a stack only sits in one slot, so putting it in another takes it out of the old one*/
+        let stack = match (ecs.read_storage::<Stackable>().get(item), ecs.read_storage::<Name>().get(item)) {
+            (Some(_), Some(name)) => Some(name.name.clone()),
+            _ => None,
+        };
+        let old_slots : Vec<u8> = self.slots.iter()
+            .filter(|(old, assigned)| **assigned == item || (stack.is_some() && self.stacks.get(*old) == stack.as_ref()))
+            .map(|(old, _assigned)| *old)
+            .collect();
+        for old in old_slots.iter() {
+            self.slots.remove(old);
+            self.stacks.remove(old);
+        }
+        self.slots.insert(slot, item);
+        match stack {
+            Some(stack) => { self.stacks.insert(slot, stack); }
+            None => { self.stacks.remove(&slot); }
+        }
+    }
+
+    /*What the slot would use right now. None if it was never filled, or the player no longer carries anything it could mean*/
+    pub fn item(&self, ecs: &World, slot: u8) -> Option<Entity> {
+        let player_entity = *ecs.fetch::<Entity>();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let carried = |item: Entity| backpack.get(item).is_some_and(|pack| pack.owner == player_entity); /*Also false for an item that has been deleted*/
+        if let Some(item) = self.slots.get(&slot).copied().filter(|item| carried(*item)) { return Some(item); }
+
+        let stack = self.stacks.get(&slot)?;
+        let names = ecs.read_storage::<Name>();
+        let stackable = ecs.read_storage::<Stackable>();
+        (&ecs.entities(), &names, &stackable).join()
+            .find(|(item, name, _stackable)| name.name == *stack && carried(*item))
+            .map(|(item, _name, _stackable)| item)
+    }
+}
@@ -0,0 +1,50 @@
+use specs::prelude::*;
+use super::{HungerClock, HungerState, SufferDamage, DamageType, gamelog::GameLog};
+
+pub struct HungerSystem {}
+
+/*How many turns each stage lasts before you get hungrier. About 600 turns from a meal to starving, which is a couple of levels*/
+pub const WELL_FED_TURNS : i32 = 200;
+const NORMAL_TURNS : i32 = 200;
+const HUNGRY_TURNS : i32 = 200;
+
+impl<'a> System<'a> for HungerSystem { /*Counts the hunger clocks down a turn, and hurts whoever is starving*/
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, HungerClock>,
+                        ReadExpect<'a, Entity>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteExpect<'a, GameLog>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut hunger_clock, player_entity, mut inflict_damage, mut log) = data;
+
+        for (entity, clock) in (&entities, &mut hunger_clock).join() {
+            let is_player = entity == *player_entity;
+            clock.duration -= 1;
+            if clock.duration > 0 { continue; }
+
+            match clock.state {
+                HungerState::WellFed => {
+                    clock.state = HungerState::Normal;
+                    clock.duration = NORMAL_TURNS;
+                    if is_player { log.add("You are no longer well fed."); }
+                }
+                HungerState::Normal => {
+                    clock.state = HungerState::Hungry;
+                    clock.duration = HUNGRY_TURNS;
+                    if is_player { log.add("You are hungry."); }
+                }
+                HungerState::Hungry => {
+                    clock.state = HungerState::Starving;
+                    clock.duration = 1; /*Starving never wears off, it just hurts every turn*/
+                    if is_player { log.add("You are starving!"); }
+                }
+                HungerState::Starving => {
+                    clock.duration = 1;
+                    if is_player { log.add("Your hunger pangs are getting painful! Find something to eat."); }
+                    SufferDamage::new_damage(&mut inflict_damage, entity, 1, DamageType::Physical);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use specs::prelude::*;
+use rltk::RGB;
+use super::{WantsToPickupItem, WantsToUseItem, WantsToDropItem, ProvidesHealing, CombatStats, Name, InBackpack, Position,
+    Consumable, InflictsDamage, particle_system::ParticleBuilder, SufferDamage, AreaOfEffect, Confusion, Equippable, Equipped, Map, gamelog::{GameLog, Logger},
+    ProvidesFood, HungerClock, HungerState, hunger_system::WELL_FED_TURNS, MagicMapper, ObfuscatedName, dungeon::IdentifiedItems, deletion_queue::ToDelete, Ranged, Charmed, Charges};
+
+pub struct ItemCollectionSystem {}
+
+pub const THROW_RANGE : i32 = 6; /*How far something can be thrown if it isn't meant to be aimed, like a potion*/
+
+/*Whether an item does anything to whatever it lands on. Those are the ones worth throwing, and they break when they do*/
+pub fn is_throwable(ecs: &World, item: Entity) -> bool {
+    ecs.read_storage::<Consumable>().get(item).is_some()
+        && (ecs.read_storage::<ProvidesHealing>().get(item).is_some()
+            || ecs.read_storage::<InflictsDamage>().get(item).is_some()
+            || ecs.read_storage::<Confusion>().get(item).is_some())
+}
+
+pub fn is_depleted(ecs: &World, item: Entity) -> bool { /*A wand with no charges left. Items without charges never run out this way*/
+    ecs.read_storage::<Charges>().get(item).is_some_and(|charges| charges.current <= 0)
+}
+
+pub fn throw_range(ecs: &World, item: Entity) -> i32 { /*Aimed items already say how far they reach*/
+    ecs.read_storage::<Ranged>().get(item).map_or(THROW_RANGE, |ranged| ranged.range)
+}
+
+/*Set by a magic mapping scroll. The scroll's effect is drawn out over several frames, so the state takes it from here once the turn is over*/
+#[derive(Default)]
+pub struct MagicMapping {
+    pub pending: bool,
+}
+
+impl<'a> System<'a> for ItemCollectionSystem { /*Moves picked up items off the map and into the picker's backpack*/
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, WantsToPickupItem>,
+                        WriteStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, InBackpack>,
+                        ReadStorage<'a, ObfuscatedName>,
+                        ReadExpect<'a, IdentifiedItems>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack, obfuscated, identified) = data;
+
+        for pickup in wants_pickup.join() {
+            positions.remove(pickup.item); /*No position means it is no longer on the map*/
+            backpack.insert(pickup.item, InBackpack { owner: pickup.collected_by }).expect("Unable to insert backpack entry");
+
+            if pickup.collected_by == *player_entity {
+                if let Some(name) = names.get(pickup.item) {
+                    Logger::new().append("You pick up the").item_name(identified.display_name(name, obfuscated.get(pickup.item))).log(&mut gamelog);
+                }
+            }
+        }
+
+        wants_pickup.clear();
+    }
+}
+
+pub struct ItemUseSystem {}
+
+impl<'a> System<'a> for ItemUseSystem { /*Applies the effect of used items, then gets rid of the ones that are used up*/
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToUseItem>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Consumable>,
+                        ReadStorage<'a, ProvidesHealing>,
+                        ReadStorage<'a, InflictsDamage>,
+                        ReadStorage<'a, AreaOfEffect>,
+                        WriteStorage<'a, Confusion>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Equippable>,
+                        WriteStorage<'a, Equipped>,
+                        WriteStorage<'a, InBackpack>,
+                        ReadStorage<'a, ProvidesFood>,
+                        WriteStorage<'a, HungerClock>,
+                        ReadStorage<'a, MagicMapper>,
+                        WriteExpect<'a, MagicMapping>,
+                        WriteExpect<'a, IdentifiedItems>,
+                        WriteExpect<'a, ToDelete>,
+                        ReadStorage<'a, Ranged>,
+                        (WriteStorage<'a, Charmed>, WriteStorage<'a, Charges>)); /*Paired up because specs only takes 26 at a time*/
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut gamelog, map, entities, mut wants_use, names, consumables, healing, inflict_damage, aoe, mut confused, mut combat_stats, mut suffer_damage, mut particle_builder, positions,
+            equippable, mut equipped, mut backpack, provides_food, mut hunger_clocks, magic_mapper, mut magic_mapping, mut identified, mut to_delete, ranged, (mut charmed, mut charges)) = data;
+
+        for (entity, useitem) in (&entities, &wants_use).join() {
+            let mut used_item = true;
+            let item_name = names.get(useitem.item).map_or("item", |n| n.name.as_str());
+            if charges.get(useitem.item).is_some_and(|charges| charges.current <= 0) { /*Nothing left in it, so nothing happens*/
+                if entity == *player_entity { gamelog.add("The wand is depleted."); }
+                continue;
+            }
+            if entity == *player_entity && consumables.get(useitem.item).is_some() { /*Using it shows what it was, so the messages below can use the real name*/
+                identified.identify(item_name);
+            }
+            let thrown = useitem.target.is_some() && ranged.get(useitem.item).is_none(); /*Aimed at a tile without being meant for aiming, so it was thrown there*/
+            if thrown && entity == *player_entity {
+                Logger::new().append("You throw the").item_name(item_name).log(&mut gamelog);
+            }
+
+            let mut targets : Vec<Entity> = Vec::new(); /*Items without a target are used on whoever is using them, aimed items hit everything on the tile*/
+            match useitem.target {
+                None => targets.push(entity),
+                Some(target) => match aoe.get(useitem.item) {
+                    None => {
+                        let idx = map.xy_idx(target.x, target.y);
+                        for mob in map.tile_content[idx].iter() {
+                            targets.push(*mob);
+                        }
+                    }
+                    Some(area_effect) => { /*Everything the blast can reach from the target tile. That includes the user if they are standing too close*/
+                        let mut blast_tiles = rltk::field_of_view(target, area_effect.radius, &*map);
+                        blast_tiles.retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
+                        for tile_idx in blast_tiles.iter() {
+                            let idx = map.xy_idx(tile_idx.x, tile_idx.y);
+                            for mob in map.tile_content[idx].iter() {
+                                if !targets.contains(mob) { targets.push(*mob); } /*Something big covers several tiles, but only gets hit once*/
+                            }
+                        }
+                    }
+                }
+            }
+
+            if let Some(can_equip) = equippable.get(useitem.item) { /*Gear is put on by whoever used it, swapping out anything already in that slot*/
+                let target_slot = can_equip.slot;
+                let target = targets[0];
+
+                let mut to_unequip : Vec<Entity> = Vec::new();
+                for (item_entity, already_equipped) in (&entities, &equipped).join() {
+                    if already_equipped.owner == target && already_equipped.slot == target_slot {
+                        to_unequip.push(item_entity);
+                    }
+                }
+                for item in to_unequip.iter() {
+                    equipped.remove(*item);
+                    backpack.insert(*item, InBackpack { owner: target }).expect("Unable to insert backpack entry");
+                    if target == *player_entity {
+                        let old_name = names.get(*item).map_or("item", |n| n.name.as_str());
+                        Logger::new().append("You unequip the").item_name(old_name).log(&mut gamelog);
+                    }
+                }
+
+                equipped.insert(useitem.item, Equipped { owner: target, slot: target_slot }).expect("Unable to insert equipped component");
+                backpack.remove(useitem.item);
+                if target == *player_entity {
+                    Logger::new().append("You equip the").item_name(item_name).log(&mut gamelog);
+                }
+            }
+
+            if magic_mapper.get(useitem.item).is_some() {
+                magic_mapping.pending = true;
+                gamelog.add("The map is revealed to you!");
+            }
+
+            if provides_food.get(useitem.item).is_some() {
+                for target in targets.iter() {
+                    if let Some(clock) = hunger_clocks.get_mut(*target) { /*Eating always fills you right up, however hungry you were*/
+                        clock.state = HungerState::WellFed;
+                        clock.duration = WELL_FED_TURNS;
+                        if entity == *player_entity {
+                            Logger::new().append("You eat the").item_name(item_name).log(&mut gamelog);
+                        }
+                    }
+                }
+            }
+
+            if let Some(healer) = healing.get(useitem.item) {
+                for target in targets.iter() {
+                    if let Some(stats) = combat_stats.get_mut(*target) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + healer.heal_amount); /*Healing can't go over max hp*/
+                        if let Some(pos) = positions.get(*target) {
+                            particle_builder.request(pos.x, pos.y, RGB::named(rltk::GREEN), RGB::named(rltk::BLACK), rltk::to_cp437('♥'), 200.0);
+                        }
+                        if entity == *player_entity && thrown {
+                            let mob_name = names.get(*target).map_or("something", |n| n.name.as_str());
+                            Logger::new().append("It splashes over").npc_name(mob_name).append("healing").healing(healer.heal_amount).append("hp").log(&mut gamelog);
+                        } else if entity == *player_entity {
+                            Logger::new().append("You drink the").item_name(item_name).append("healing").healing(healer.heal_amount).append("hp").log(&mut gamelog);
+                        }
+                    }
+                }
+            }
+
+            if let Some(damage) = inflict_damage.get(useitem.item) {
+                used_item = false; /*Only used up if it actually hit something*/
+                for mob in targets.iter() {
+                    if combat_stats.get(*mob).is_none() { continue; } /*Items and the like can't be hurt*/
+                    SufferDamage::new_damage(&mut suffer_damage, *mob, damage.damage, damage.damage_type);
+                    if let Some(pos) = positions.get(*mob) {
+                        particle_builder.request(pos.x, pos.y, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), rltk::to_cp437('‼'), 200.0);
+                    }
+                    if entity == *player_entity { /*Every hit gets its own message, so you can tell who got caught in a blast*/
+                        let mob_name = names.get(*mob).map_or("something", |n| n.name.as_str());
+                        if thrown {
+                            Logger::new().append("It bursts over").npc_name(mob_name).append("inflicting").damage(damage.damage).append("damage").log(&mut gamelog);
+                        } else {
+                            Logger::new().append("You use").item_name(item_name).append("on").npc_name(mob_name).append("inflicting").damage(damage.damage).append("damage").log(&mut gamelog);
+                        }
+                    }
+                    used_item = true;
+                }
+            }
+
+            let causes_confusion = confused.get(useitem.item).map(|confusion| confusion.turns);
+            if let Some(turns) = causes_confusion {
+                used_item = false;
+                for mob in targets.iter() {
+                    if combat_stats.get(*mob).is_none() { continue; } /*Only things that fight can be confused*/
+                    confused.insert(*mob, Confusion { turns }).expect("Unable to insert status");
+                    if let Some(pos) = positions.get(*mob) {
+                        particle_builder.request(pos.x, pos.y, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), rltk::to_cp437('?'), 200.0);
+                    }
+                    if entity == *player_entity {
+                        let mob_name = names.get(*mob).map_or("something", |n| n.name.as_str());
+                        Logger::new().append("The").npc_name(mob_name.to_lowercase()).append("is confused!").log(&mut gamelog);
+                    }
+                    used_item = true;
+                }
+            }
+
+            let charms = charmed.get(useitem.item).map(|charm| charm.turns);
+            if let Some(turns) = charms {
+                used_item = false;
+                for mob in targets.iter() {
+                    if *mob == *player_entity || combat_stats.get(*mob).is_none() { continue; } /*Only monsters can be talked round*/
+                    charmed.insert(*mob, Charmed { turns }).expect("Unable to insert status");
+                    if let Some(pos) = positions.get(*mob) {
+                        particle_builder.request(pos.x, pos.y, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK), rltk::to_cp437('♥'), 200.0);
+                    }
+                    if entity == *player_entity {
+                        let mob_name = names.get(*mob).map_or("something", |n| n.name.as_str());
+                        Logger::new().append("The").npc_name(mob_name.to_lowercase()).append("is charmed, and fights for you!").log(&mut gamelog);
+                    }
+                    used_item = true;
+                }
+            }
+
+            if used_item {
+                if let Some(charges) = charges.get_mut(useitem.item) { charges.current -= 1; }
+            }
+
+            if (used_item || thrown) && consumables.get(useitem.item).is_some() { /*Something thrown breaks wherever it lands, even if it missed everyone*/
+                to_delete.push(useitem.item);
+            }
+        }
+
+        wants_use.clear();
+    }
+}
+
+pub struct ItemDropSystem {}
+
+impl<'a> System<'a> for ItemDropSystem { /*Takes dropped items out of the backpack and puts them on the floor where the dropper is standing*/
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToDropItem>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, InBackpack>,
+                        ReadStorage<'a, ObfuscatedName>,
+                        ReadExpect<'a, IdentifiedItems>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut gamelog, entities, mut wants_drop, names, mut positions, mut backpack, obfuscated, identified) = data;
+
+        for (entity, to_drop) in (&entities, &wants_drop).join() {
+            let dropper_pos = match positions.get(entity) {
+                Some(pos) => Position { x: pos.x, y: pos.y },
+                None => continue, /*Nowhere to drop it*/
+            };
+            positions.insert(to_drop.item, dropper_pos).expect("Unable to insert position"); /*Having a position again puts it back on the map*/
+            backpack.remove(to_drop.item);
+
+            if entity == *player_entity {
+                if let Some(name) = names.get(to_drop.item) {
+                    Logger::new().append("You drop the").item_name(identified.display_name(name, obfuscated.get(to_drop.item))).log(&mut gamelog);
+                }
+            }
+        }
+
+        wants_drop.clear();
+    }
+}
@@ -0,0 +1,181 @@
+use rltk::VirtualKeyCode;
+use std::collections::HashMap;
+
+pub const KEYBINDINGS_FILE : &str = "./keys.toml";
+
+/*Everything the player can ask for from the keyboard during their turn*/
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    Wait,
+    Rest, /*Keep waiting until healed, or until something happens*/
+    Explore, /*Walk to the nearest unexplored spot, over and over until the level is done or something turns up*/
+    Descend, /*Goes down the stairs if you're on them, otherwise waits*/
+    Ascend,
+    PickUp,
+    QuickUse, /*Use the one potion or scroll to hand, from the floor or the pack, without going through the inventory*/
+    Search, /*Look for hidden traps next to you, which takes a turn*/
+    Inventory,
+    Drop,
+    Throw, /*Aim a potion, or anything else with an effect, at a tile instead of using it on yourself*/
+    Fire, /*Attack something out of reach of your fists with a spear or a bow*/
+    Menu,
+    ToggleThreat,
+    ToggleMinimap,
+    Examine, /*Look around with a cursor, without taking a turn*/
+    ShowLog, /*Read back through older messages*/
+    AssignQuickslot, /*Pick an item from the pack, then the number key it goes on*/
+    Quickslot1, /*Use whatever is on that number key*/
+    Quickslot2,
+    Quickslot3,
+    Quickslot4,
+    Quickslot5,
+}
+
+impl Action {
+    pub fn direction(self) -> Option<(i32, i32)> { /*Which way a move action points, for anything else that wants to be steered with the movement keys*/
+        match self {
+            Action::MoveLeft => Some((-1, 0)),
+            Action::MoveRight => Some((1, 0)),
+            Action::MoveUp => Some((0, -1)),
+            Action::MoveDown => Some((0, 1)),
+            Action::MoveUpLeft => Some((-1, -1)),
+            Action::MoveUpRight => Some((1, -1)),
+            Action::MoveDownLeft => Some((-1, 1)),
+            Action::MoveDownRight => Some((1, 1)),
+            _ => None,
+        }
+    }
+
+    pub fn quickslot(self) -> Option<u8> { /*Which hotbar slot a quickslot action uses*/
+        match self {
+            Action::Quickslot1 => Some(1),
+            Action::Quickslot2 => Some(2),
+            Action::Quickslot3 => Some(3),
+            Action::Quickslot4 => Some(4),
+            Action::Quickslot5 => Some(5),
+            _ => None,
+        }
+    }
+}
+
+const ACTIONS : [Action; 31] = [Action::MoveLeft, Action::MoveRight, Action::MoveUp, Action::MoveDown,
+    Action::MoveUpLeft, Action::MoveUpRight, Action::MoveDownLeft, Action::MoveDownRight,
+    Action::Wait, Action::Rest, Action::Explore, Action::Descend, Action::Ascend, Action::PickUp, Action::QuickUse, Action::Search, Action::Inventory, Action::Drop, Action::Throw, Action::Fire, Action::Menu, Action::ToggleThreat, Action::ToggleMinimap, Action::Examine, Action::ShowLog,
+    Action::AssignQuickslot, Action::Quickslot1, Action::Quickslot2, Action::Quickslot3, Action::Quickslot4, Action::Quickslot5];
+
+/*The keys that can be named in the bindings file. They are spelled the same way as in rltk, so "Numpad4" or "Left"*/
+const KEYS : [VirtualKeyCode; 64] = [
+    VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E, VirtualKeyCode::F,
+    VirtualKeyCode::G, VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J, VirtualKeyCode::K, VirtualKeyCode::L,
+    VirtualKeyCode::M, VirtualKeyCode::N, VirtualKeyCode::O, VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R,
+    VirtualKeyCode::S, VirtualKeyCode::T, VirtualKeyCode::U, VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X,
+    VirtualKeyCode::Y, VirtualKeyCode::Z,
+    VirtualKeyCode::Key0, VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5, VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+    VirtualKeyCode::Numpad0, VirtualKeyCode::Numpad1, VirtualKeyCode::Numpad2, VirtualKeyCode::Numpad3, VirtualKeyCode::Numpad4,
+    VirtualKeyCode::Numpad5, VirtualKeyCode::Numpad6, VirtualKeyCode::Numpad7, VirtualKeyCode::Numpad8, VirtualKeyCode::Numpad9,
+    VirtualKeyCode::Left, VirtualKeyCode::Right, VirtualKeyCode::Up, VirtualKeyCode::Down,
+    VirtualKeyCode::Escape, VirtualKeyCode::Return, VirtualKeyCode::Space, VirtualKeyCode::Tab, VirtualKeyCode::Back,
+    VirtualKeyCode::Period, VirtualKeyCode::Comma, VirtualKeyCode::Slash, VirtualKeyCode::Semicolon, VirtualKeyCode::Apostrophe,
+    VirtualKeyCode::Minus, VirtualKeyCode::Equals, VirtualKeyCode::LBracket, VirtualKeyCode::RBracket,
+];
+
+/*Which action every key does. Stored as a resource so player_input can look keys up*/
+pub struct Keybindings {
+    bindings : HashMap<VirtualKeyCode, Action>,
+}
+
+impl Keybindings {
+    pub fn defaults() -> Keybindings { /*Arrows, the numpad and the Vi keys all move*/
+        let mut kb = Keybindings { bindings : HashMap::new() };
+        kb.bind(Action::MoveLeft, &[VirtualKeyCode::Left, VirtualKeyCode::Numpad4, VirtualKeyCode::H]);
+        kb.bind(Action::MoveRight, &[VirtualKeyCode::Right, VirtualKeyCode::Numpad6, VirtualKeyCode::L]);
+        kb.bind(Action::MoveUp, &[VirtualKeyCode::Up, VirtualKeyCode::Numpad8, VirtualKeyCode::K]);
+        kb.bind(Action::MoveDown, &[VirtualKeyCode::Down, VirtualKeyCode::Numpad2, VirtualKeyCode::J]);
+        kb.bind(Action::MoveUpLeft, &[VirtualKeyCode::Numpad7, VirtualKeyCode::Y]);
+        kb.bind(Action::MoveUpRight, &[VirtualKeyCode::Numpad9, VirtualKeyCode::U]);
+        kb.bind(Action::MoveDownLeft, &[VirtualKeyCode::Numpad1, VirtualKeyCode::B]);
+        kb.bind(Action::MoveDownRight, &[VirtualKeyCode::Numpad3, VirtualKeyCode::N]);
+        kb.bind(Action::Wait, &[VirtualKeyCode::Numpad5]);
+        kb.bind(Action::Rest, &[VirtualKeyCode::R]);
+        kb.bind(Action::Explore, &[VirtualKeyCode::O]);
+        kb.bind(Action::Descend, &[VirtualKeyCode::Period]);
+        kb.bind(Action::Ascend, &[VirtualKeyCode::Comma]); /*The same key as < on most keyboards*/
+        kb.bind(Action::PickUp, &[VirtualKeyCode::G]);
+        kb.bind(Action::QuickUse, &[VirtualKeyCode::Q]); /*Q for quaff*/
+        kb.bind(Action::Search, &[VirtualKeyCode::S]);
+        kb.bind(Action::Inventory, &[VirtualKeyCode::I]);
+        kb.bind(Action::Drop, &[VirtualKeyCode::D]);
+        kb.bind(Action::Throw, &[VirtualKeyCode::V]);
+        kb.bind(Action::Fire, &[VirtualKeyCode::F]);
+        kb.bind(Action::Menu, &[VirtualKeyCode::Escape]);
+        kb.bind(Action::ToggleThreat, &[VirtualKeyCode::T]);
+        kb.bind(Action::ToggleMinimap, &[VirtualKeyCode::M]);
+        kb.bind(Action::Examine, &[VirtualKeyCode::X]);
+        kb.bind(Action::ShowLog, &[VirtualKeyCode::P]); /*L would be the obvious key, but it already moves right*/
+        kb.bind(Action::AssignQuickslot, &[VirtualKeyCode::A]);
+        kb.bind(Action::Quickslot1, &[VirtualKeyCode::Key1]); /*The numbers along the top, since the numpad moves*/
+        kb.bind(Action::Quickslot2, &[VirtualKeyCode::Key2]);
+        kb.bind(Action::Quickslot3, &[VirtualKeyCode::Key3]);
+        kb.bind(Action::Quickslot4, &[VirtualKeyCode::Key4]);
+        kb.bind(Action::Quickslot5, &[VirtualKeyCode::Key5]);
+        kb
+    }
+
+    fn bind(&mut self, action: Action, keys: &[VirtualKeyCode]) { /*Replaces whatever keys the action had before*/
+        self.bindings.retain(|_key, bound| *bound != action);
+        for key in keys.iter() {
+            self.bindings.insert(*key, action);
+        }
+    }
+
+    pub fn action_for(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+
+    /*Starts from the defaults and applies whatever the file changes. A missing file just means the defaults.
+    The file is a flat list of lines like: MoveLeft = ["Left", "H"] or PickUp = "G", anything after a # is a comment*/
+    pub fn load() -> Keybindings {
+        let mut kb = Keybindings::defaults();
+        let contents = match std::fs::read_to_string(KEYBINDINGS_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return kb,
+        };
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() || line.starts_with('[') { continue; } /*Section headers don't mean anything here*/
+
+            let (name, value) = match line.split_once('=') {
+                Some(parts) => parts,
+                None => { eprintln!("Warning: {} line {} is not of the form Action = \"Key\"", KEYBINDINGS_FILE, line_number + 1); continue; }
+            };
+            let name = name.trim();
+            let action = match ACTIONS.iter().find(|action| format!("{:?}", action) == name) {
+                Some(action) => *action,
+                None => { eprintln!("Warning: {} line {} has unknown action {}", KEYBINDINGS_FILE, line_number + 1, name); continue; }
+            };
+
+            let mut keys = Vec::new();
+            for key_name in value.trim().trim_start_matches('[').trim_end_matches(']').split(',') {
+                let key_name = key_name.trim().trim_matches('"');
+                if key_name.is_empty() { continue; }
+                match KEYS.iter().find(|key| format!("{:?}", key) == key_name) {
+                    Some(key) => keys.push(*key),
+                    None => eprintln!("Warning: {} line {} has unknown key {}", KEYBINDINGS_FILE, line_number + 1, key_name),
+                }
+            }
+            if keys.is_empty() { continue; } /*Don't leave an action with no keys at all just because of a typo*/
+            kb.bind(action, &keys);
+        }
+        kb
+    }
+}
@@ -4,120 +4,855 @@ This means everything with physical form is an entitiy that implements one or mo
 The ECS is provided from the specs library, and it is a central control element.
 */
 
-use rltk::{GameState, Rltk, RGB, VirtualKeyCode};
+use rltk::{GameState, Rltk};
 use specs::prelude::*;
-use std::cmp::{max, min};
-use specs_derive::Component;
+use specs::saveload::SimpleMarkerAllocator;
+use serde::{Serialize, Deserialize};
+mod components;
+pub use components::*;
+mod map;
+mod map_theme;
+pub use map::*;
+mod visibility_system;
+use visibility_system::VisibilitySystem;
+mod melee_combat_system;
+use melee_combat_system::MeleeCombatSystem;
+mod damage_system;
+use damage_system::{DamageSystem, DeadCleanupSystem};
+mod monster_ai_system;
+use monster_ai_system::MonsterAI;
+mod map_indexing_system;
+use map_indexing_system::MapIndexingSystem;
+mod hunger_system;
+mod energy_system;
+mod deletion_queue;
+use energy_system::EnergySystem;
+mod trigger_system;
+mod terrain_system;
+use terrain_system::TerrainSystem;
+use trigger_system::TriggerSystem;
+use hunger_system::HungerSystem;
+mod damage_over_time_system;
+use damage_over_time_system::DamageOverTimeSystem;
+mod gamelog;
+mod gui;
+mod draw;
+mod camera;
+mod particle_system;
+mod game_time;
+mod keybindings;
+mod stats;
+mod dungeon;
+mod factions;
+mod difficulty;
+mod settings;
+use keybindings::{Keybindings, Action};
+mod rect;
+pub use rect::Rect;
+mod spawner;
+mod raws;
+mod random_table;
+mod saveload_system;
+mod inventory_system;
+mod hotbar;
+mod movement;
+#[cfg(feature = "debug")]
+mod debug_console;
+use inventory_system::{ItemCollectionSystem, ItemUseSystem, ItemDropSystem};
 
 
-/*Derive is a library short-hand for implementing the desired interface for that struct. So position is a component (building block) for entities such as players*/
-#[derive(Component)]
-struct Position {
-    x: i32,
-    y: i32,
-}
-
-#[derive(Component)] 
-struct Renderable {
-    glyph: rltk::FontCharType,
-    fg: RGB,
-    bg: RGB,
-}
+/*The world only moves forward when the player does something. Paused means we are waiting for input, Running means the systems should run a turn, and the rest are menus*/
+#[derive(PartialEq, Clone)]
+pub enum RunState { Paused, Running, ShowInventory, ShowDropItem, ShowThrowItem, ShowQuickslotItem, AssignQuickslot { item: Entity }, ShowTargeting { range: i32, item: Entity, cursor: rltk::Point }, NextLevel, PreviousLevel, Traveling { path: Vec<usize>, step: usize, explore: bool }, Confirm { prompt: String, on_yes: ConfirmAction }, Resting, MagicMapReveal { row: i32 }, Examine { cursor: rltk::Point }, ShowLog { scroll: usize }, NameEntry { buffer: String }, MainMenu { menu_selection: gui::MainMenuSelection }, GameOver,
+    #[cfg(feature = "debug")] DebugConsole { buffer: String } }
 
-#[derive(Component)]
-struct LeftMover {
+/*What answering yes to a Confirm box goes on to do. Answering no goes back to where the question was asked from, and nothing else happens*/
+#[derive(PartialEq, Clone)]
+pub enum ConfirmAction { Descend, DeleteSlot { slot: u8 }, StepOnHazard { path: Vec<usize>, step: usize, explore: bool } }
 
+impl ConfirmAction {
+    fn declined(&self) -> RunState {
+        match self {
+            ConfirmAction::DeleteSlot { slot } => RunState::MainMenu { menu_selection: gui::MainMenuSelection::SaveSlot(*slot) },
+            ConfirmAction::Descend | ConfirmAction::StepOnHazard { .. } => RunState::Paused, /*Still on the stairs, or stopped at the edge of the trap, and no turn taken*/
+        }
+    }
 }
- 
-#[derive(Component, Debug)]
-struct Player {
 
-}
+/*Something the player has asked to do with their turn. Input only queues one of these, and the turn that runs next carries it out,
+so however many keys land in one frame the world only ever moves once*/
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum PlayerAction { Move { dx: i32, dy: i32 }, Wait, PickUp, Search, Wade }
 
 /*A world is an instruction from the library Specs that can register components. Can be considered like a constructor*/
 struct State {
-    ecs: World
+    ecs: World,
+    runstate: RunState,
+    show_threat: bool, /*Colour monsters by how dangerous they are*/
+    show_minimap: bool,
+    pending_action: Option<PlayerAction>, /*What the player asked for this frame, used up by the next turn*/
+    menu_message: Option<String>, /*Shown under the main menu, like why a save wouldn't load*/
+    save_slot: u8, /*Which slot Save Game and Load Game use*/
+    save_slots: Vec<Option<saveload_system::SlotSummary>>, /*What is in each slot, kept here so the menu doesn't read every save file each frame*/
+    difficulty: difficulty::Difficulty, /*Picked on the menu, and handed to the next new game. The game being played keeps its own*/
 }
 
+const DEFAULT_SEED : u64 = 1337; /*Used when no seed is given in the environment*/
+
 struct LeftWalker {
 
 }
 
 fn main() -> rltk::BError {
     use rltk::RltkBuilder; /*This is only used in main so just include in this scope*/
-    let context = RltkBuilder::simple80x50() /*Build the window*/
+    let settings = settings::Settings::load(); /*Read settings.toml if there is one, otherwise an 80x50 window with the built in font*/
+    let builder = match &settings.font {
+        None => RltkBuilder::simple(settings.columns, settings.rows)?,
+        Some(font) => RltkBuilder::new() /*Same as simple, but with a different font file and character size*/
+            .with_dimensions(settings.columns, settings.rows)
+            .with_tile_dimensions(font.width, font.height)
+            .with_font(&font.file, font.width, font.height)
+            .with_simple_console(settings.columns, settings.rows, &font.file),
+    };
+    let context = builder /*Build the window*/
         .with_title("KazooGame") /*Title of the window*/
         .build()?; /*Build the window with the options so far. ? is an operator the lets rust know this can fail, and should return early if an error occurs*/
-    let mut gs = State {
-        ecs: World::new() /*gs is the GameState. It instantiates a new world*/
+    let seed = std::env::var("KAZOO_SEED").ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or(DEFAULT_SEED); /*Set KAZOO_SEED to replay a specific dungeon*/
+    rltk::console::log(format!("Using seed {}", seed));
+    let gs = State::new(settings, Keybindings::load(), raws::RawMaster::load(), seed); /*Read keys.toml and spawns.ron if they are there, otherwise the defaults built into the game*/
+    rltk::main_loop(context, gs)/*main_loop comes from the library*/
+}
+
+/*How many times in a row the player has walked into a wall, deep water or lava. Only used to stop the message from filling the log*/
+struct WallBumps {
+    consecutive: u32,
+}
+
+const BUMP_MESSAGE_EVERY: u32 = 5; /*The first bump is always mentioned, then every fifth one after that*/
+
+fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) -> RunState { /*Returns Paused if the player didn't actually do anything, so bumping into things is free*/
+    let mut positions = ecs.write_storage::<Position>(); /*Gain write access to the entity's position*/
+    let mut players = ecs.write_storage::<Player>(); /*Gain write access to the entity's player component*/
+    let mut viewsheds = ecs.write_storage::<Viewshed>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut blocks_tile = ecs.write_storage::<BlocksTile>();
+    let mut blocks_visibility = ecs.write_storage::<BlocksVisibility>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let entities = ecs.entities();
+    let mut map = ecs.fetch_mut::<Map>(); /*Fetch the map resource so we can check for walls and whatever is standing there*/
+    let mut bumps = ecs.write_resource::<WallBumps>();
+    let charmed = ecs.read_storage::<Charmed>();
+    let mut moved = ecs.write_resource::<movement::MovedChannel>();
+    let mut swapped : Option<(Entity, usize, i32, i32)> = None; /*A charmed monster the player walked into, where it was and the tile it moves back to*/
+
+    for (entity, _player, pos, viewshed) in (&entities, &mut players, &mut positions, &mut viewsheds).join() { /*Return only entities with player components*/
+        let destination = map.clamp_point(rltk::Point::new(pos.x + delta_x, pos.y + delta_y)); /*Never try to step off the edge of the map*/
+        if destination.x == pos.x && destination.y == pos.y { return RunState::Paused; } /*Pushing against the edge of the map does nothing*/
+        let destination_idx = map.xy_idx(destination.x, destination.y);
+
+        let closed_door = map.tile_content[destination_idx].iter().copied()
+            .find(|target| doors.get(*target).is_some_and(|door| !door.open));
+        if let Some(door_entity) = closed_door { /*Walking into a closed door opens it, which takes the turn*/
+            if let Some(door) = doors.get_mut(door_entity) { door.open = true; }
+            blocks_tile.remove(door_entity);
+            blocks_visibility.remove(door_entity);
+            if let Some(render) = renderables.get_mut(door_entity) { render.glyph = rltk::to_cp437('/'); }
+            map.blocked[destination_idx] = false; /*Don't wait for the indexing system, the doorway is clear right now. The visibility system sees the door no longer blocks sight*/
+            viewshed.dirty = true;
+            bumps.consecutive = 0;
+            return RunState::Running;
+        }
+
+        let ally = map.tile_content[destination_idx].iter().copied().find(|target| charmed.get(*target).is_some()); /*Walking into a charmed monster swaps places with it*/
+        if ally.is_some_and(|ally| ecs.read_storage::<Size>().get(ally).is_some()) { return RunState::Paused; } /*unless it is too big to fit where the player was*/
+        for potential_target in map.tile_content[destination_idx].iter() { /*Walking into something that can fight means attacking it instead of moving*/
+            if combat_stats.get(*potential_target).is_some() && Some(*potential_target) != ally {
+                wants_to_melee.insert(entity, WantsToMelee { target: *potential_target }).expect("Add target failed");
+                bumps.consecutive = 0;
+                return RunState::Running;
+            }
+        }
+
+        let squeezing = delta_x != 0 && delta_y != 0 /*Moving diagonally needs both of the tiles beside the move to be open, so you can't slip between two walls*/
+            && (map.tiles[map.xy_idx(destination.x, pos.y)] == TileType::Wall || map.tiles[map.xy_idx(pos.x, destination.y)] == TileType::Wall);
+
+        let refusal = match map.tiles[destination_idx] { /*Nothing stops you trying, but you think better of it*/
+            TileType::DeepWater => Some("The water is too deep to wade into."),
+            TileType::Lava => Some("The lava is far too hot to walk on."),
+            TileType::Wall => Some("You bump into the wall."),
+            _ if squeezing => Some("You bump into the wall."),
+            _ => None,
+        };
+        if let Some(message) = refusal { /*Holding the key down repeats the message, so only some of them go in the log*/
+            if bumps.consecutive % BUMP_MESSAGE_EVERY == 0 {
+                ecs.write_resource::<gamelog::GameLog>().add(message);
+            }
+            bumps.consecutive += 1;
+            return RunState::Paused;
+        }
+
+        if !map.blocked[destination_idx] || ally.is_some() { /*Only move if nothing is in the way*/
+            if let Some(ally) = ally { swapped = Some((ally, destination_idx, pos.x, pos.y)); }
+            pos.x = destination.x; /*Move relevant entities (only the player), already kept inside the map*/
+            pos.y = destination.y;
+
+            moved.single_write(movement::MovedEvent { entity, x: pos.x, y: pos.y }); /*So what they can see is worked out again, and any trap here goes off*/
+            bumps.consecutive = 0;
+
+            let mut player_pos = ecs.write_resource::<rltk::Point>(); /*Keep the player's position resource up to date for the monsters*/
+            player_pos.x = pos.x;
+            player_pos.y = pos.y;
+            if swapped.is_none() { return RunState::Running; }
+        }
+    }
+    if let Some((ally, from, x, y)) = swapped { /*Done once the player's own position is let go of*/
+        if let Some(ally_pos) = positions.get_mut(ally) {
+            ally_pos.x = x;
+            ally_pos.y = y;
+        }
+        let to = map.xy_idx(x, y);
+        map.move_entity(ally, from, to, true);
+        moved.single_write(movement::MovedEvent { entity: ally, x, y });
+        return RunState::Running;
+    }
+    RunState::Paused /*Something that can't be fought is in the way*/
+}
+
+fn get_item(ecs: &mut World) { /*Pick up whatever item the player is standing on*/
+    let player_pos = ecs.fetch::<rltk::Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let items = ecs.read_storage::<Item>();
+    let map = ecs.fetch::<Map>();
+    let mut gamelog = ecs.fetch_mut::<gamelog::GameLog>();
+
+    let target_item = map.tile_content[map.xy_idx(player_pos.x, player_pos.y)].iter()
+        .find(|entity| items.get(**entity).is_some());
+
+    match target_item {
+        None => gamelog.add("There is nothing here to pick up."),
+        Some(item) => {
+            let mut pickup = ecs.write_storage::<WantsToPickupItem>();
+            pickup.insert(*player_entity, WantsToPickupItem { collected_by: *player_entity, item: *item }).expect("Unable to insert want to pickup");
+        }
+    }
+}
+
+/*Uses the consumable the player is standing on or carrying, if there is only one. With more than one to choose from it opens the inventory instead.
+Equipment never counts, only things that get used up*/
+fn quick_use(ecs: &mut World) -> RunState {
+    let candidates : Vec<Entity> = {
+        let player_pos = ecs.fetch::<rltk::Point>();
+        let player_entity = ecs.fetch::<Entity>();
+        let map = ecs.fetch::<Map>();
+        let consumables = ecs.read_storage::<Consumable>();
+        let items = ecs.read_storage::<Item>();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let entities = ecs.entities();
+        let on_floor = map.tile_content[map.xy_idx(player_pos.x, player_pos.y)].iter().copied()
+            .filter(|entity| items.get(*entity).is_some());
+        let carried = (&entities, &backpack).join()
+            .filter(|(_entity, pack)| pack.owner == *player_entity)
+            .map(|(entity, _pack)| entity);
+        on_floor.chain(carried).filter(|entity| consumables.get(*entity).is_some()).collect()
     };
-    gs.ecs.register::<Position>(); /*Register all the components that an entity can have*/
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<LeftMover>();
-    gs.ecs.register::<Player>();
-
-    gs.ecs /*This should make sense by itself. An entity is created with the desired traits, such as position and it is a player*/
-        .create_entity() 
-        .with(Position { x: 40, y: 25 })
-        .with(Renderable {
-            glyph: rltk::to_cp437('@'),
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
-        })
-        .with(Player{})
-        .build(); /*Build the entity*/
-
-    for i in 0..10 { /*Create 10 entities with these relevant traits*/
-        gs.ecs
-        .create_entity()
-        .with(Position { x: i * 7, y: 20 })
-        .with(Renderable {
-            glyph: rltk::to_cp437('☺'),
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
+    match candidates.as_slice() {
+        [] => {
+            ecs.write_resource::<gamelog::GameLog>().add("You have nothing to use.");
+            RunState::Paused
+        }
+        [item] => {
+            let range = ecs.read_storage::<Ranged>().get(*item).map(|ranged| ranged.range);
+            if let Some(range) = range { /*Still needs aiming, so skip straight to that*/
+                return RunState::ShowTargeting { range, item: *item, cursor: gui::initial_target(ecs, range) };
+            }
+            let player_entity = *ecs.fetch::<Entity>();
+            ecs.write_storage::<WantsToUseItem>().insert(player_entity, WantsToUseItem { item: *item, target: None }).expect("Unable to insert intent");
+            RunState::Running
+        }
+        _ => RunState::ShowInventory,
+    }
+}
+
+/*Uses an item the player is carrying, the same way as picking it from the inventory*/
+fn use_from_pack(ecs: &mut World, item: Entity) -> RunState {
+    if inventory_system::is_depleted(ecs, item) { /*No point aiming it, and trying doesn't cost a turn*/
+        ecs.write_resource::<gamelog::GameLog>().add("The wand is depleted.");
+        return RunState::Paused;
+    }
+    let range = ecs.read_storage::<Ranged>().get(item).map(|ranged| ranged.range);
+    if let Some(range) = range { /*Aimed items need a target picked first*/
+        return RunState::ShowTargeting { range, item, cursor: gui::initial_target(ecs, range) };
+    }
+    let player_entity = *ecs.fetch::<Entity>(); /*Using an item takes a turn*/
+    ecs.write_storage::<WantsToUseItem>().insert(player_entity, WantsToUseItem { item, target: None }).expect("Unable to insert intent");
+    RunState::Running
+}
+
+fn use_quickslot(ecs: &mut World, slot: u8) -> RunState { /*An empty slot, or one whose item is gone, costs nothing*/
+    let item = ecs.fetch::<hotbar::Hotbar>().item(ecs, slot);
+    match item {
+        Some(item) => use_from_pack(ecs, item),
+        None => {
+            ecs.write_resource::<gamelog::GameLog>().add(format!("Nothing is on key {}.", slot));
+            RunState::Paused
+        }
+    }
+}
+
+const SEARCH_CHANCE_IN_SIX : i32 = 4; /*How likely each trap next to you is to be found by one turn of searching*/
+
+fn search_for_traps(ecs: &mut World) { /*Spend a turn looking around. Each hidden thing next to the player might be spotted*/
+    let player_pos = *ecs.fetch::<rltk::Point>();
+    let mut found : Vec<Entity> = Vec::new();
+    {
+        let map = ecs.fetch::<Map>();
+        let hidden = ecs.read_storage::<Hidden>();
+        let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
+        for y in player_pos.y - 1..=player_pos.y + 1 {
+            for x in player_pos.x - 1..=player_pos.x + 1 {
+                if !map.in_bounds(x, y) { continue; }
+                for entity in map.tile_content[map.xy_idx(x, y)].iter() {
+                    if hidden.get(*entity).is_some() && rng.roll_dice(1, 6) <= SEARCH_CHANCE_IN_SIX {
+                        found.push(*entity);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut hidden = ecs.write_storage::<Hidden>();
+    let names = ecs.read_storage::<Name>();
+    let mut gamelog = ecs.fetch_mut::<gamelog::GameLog>();
+    if found.is_empty() { gamelog.add("You search around you, but find nothing."); }
+    for entity in found.iter() {
+        hidden.remove(*entity);
+        let name = names.get(*entity).map_or("something", |n| n.name.as_str());
+        gamelog::Logger::new().append("You spot a").item_name(name).log(&mut gamelog);
+    }
+}
+
+fn monster_in_view(ecs: &World) -> bool { /*Can the player see anything that might want to fight them?*/
+    let player_entity = ecs.fetch::<Entity>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let monsters = ecs.read_storage::<Monster>();
+    let map = ecs.fetch::<Map>();
+
+    match viewsheds.get(*player_entity) {
+        Some(viewshed) => viewshed.visible_indices(&map)
+            .any(|idx| map.tile_content[idx].iter().any(|entity| monsters.get(*entity).is_some())),
+        None => false,
+    }
+}
+
+fn skip_turn(ecs: &mut World) { /*Let the world take a turn without doing anything. Resting heals a little, but only if nothing is watching and you aren't hungry*/
+    let hungry = ecs.read_storage::<HungerClock>().get(*ecs.fetch::<Entity>())
+        .is_some_and(|clock| clock.state == HungerState::Hungry || clock.state == HungerState::Starving);
+    if !monster_in_view(ecs) && !hungry {
+        let player_entity = ecs.fetch::<Entity>();
+        let mut combat_stats = ecs.write_storage::<CombatStats>();
+        if let Some(stats) = combat_stats.get_mut(*player_entity) {
+            if stats.hp < stats.max_hp { /*No point in resting at full health*/
+                stats.hp = i32::min(stats.max_hp, stats.hp + 1);
+                ecs.write_resource::<gamelog::GameLog>().add("You rest and recover.");
+            }
+        }
+    }
+}
+
+fn rest_interruption(ecs: &World) -> Option<&'static str> { /*Why resting should stop, if it should*/
+    let player_entity = ecs.fetch::<Entity>();
+    if ecs.read_storage::<HungerClock>().get(*player_entity).is_some_and(|clock| clock.state == HungerState::Starving) {
+        return Some("You are too hungry to rest.");
+    }
+    if monster_in_view(ecs) {
+        return Some("You can't rest with enemies nearby.");
+    }
+    if ecs.read_storage::<CombatStats>().get(*player_entity).is_none_or(|stats| stats.hp >= stats.max_hp) {
+        return Some("You are fully rested.");
+    }
+    None
+}
+
+fn try_next_level(ecs: &mut World) -> bool { /*Only works when standing on the stairs. Anywhere else the same key waits instead, so there's nothing to complain about*/
+    let player_pos = ecs.fetch::<rltk::Point>();
+    let map = ecs.fetch::<Map>();
+    map.tiles[map.xy_idx(player_pos.x, player_pos.y)] == TileType::DownStairs
+}
+
+fn badly_wounded(ecs: &World) -> bool { /*Under half health, where going somewhere new and unexplored deserves a second thought*/
+    let player_entity = ecs.fetch::<Entity>();
+    ecs.read_storage::<CombatStats>().get(*player_entity).is_some_and(|stats| stats.hp * 2 < stats.max_hp)
+}
+
+fn try_previous_level(ecs: &mut World) -> bool {
+    let player_pos = ecs.fetch::<rltk::Point>();
+    let map = ecs.fetch::<Map>();
+    if map.tiles[map.xy_idx(player_pos.x, player_pos.y)] == TileType::UpStairs {
+        true
+    } else {
+        ecs.fetch_mut::<gamelog::GameLog>().add("There is no way up from here.");
+        false
+    }
+}
+
+/*Traps the player has found. Their own routes go round these if they can, monsters don't know to*/
+fn known_hazards(ecs: &World, map: &Map) -> std::collections::HashSet<usize> {
+    let triggers = ecs.read_storage::<EntryTrigger>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let positions = ecs.read_storage::<Position>();
+    (&triggers, &positions, !&hidden).join().map(|(_trigger, pos, _hidden)| map.xy_idx(pos.x, pos.y)).collect()
+}
+
+fn hazard_prompt(ecs: &World, idx: usize) -> String { /*Asked when travelling would step onto a trap the player knows about, because there was no way round it*/
+    let map = ecs.fetch::<Map>();
+    let names = ecs.read_storage::<Name>();
+    let triggers = ecs.read_storage::<EntryTrigger>();
+    let name = map.tile_content[idx].iter().find(|entity| triggers.get(**entity).is_some())
+        .and_then(|entity| names.get(*entity))
+        .map_or("trap".to_string(), |name| name.name.to_lowercase());
+    format!("The only way on is over the {}. Step onto it?", name)
+}
+
+fn try_start_travel(ecs: &mut World, target: rltk::Point) -> RunState { /*Works out a path to a clicked tile. Tiles that haven't been seen or can't be reached are ignored*/
+    let mut map = ecs.write_resource::<Map>();
+    let player_pos = ecs.fetch::<rltk::Point>();
+    if !map.in_bounds(target.x, target.y) { return RunState::Paused; } /*Past the edge of a map smaller than the screen*/
+    let target_idx = map.xy_idx(target.x, target.y);
+    if !map.revealed_tiles[target_idx] { return RunState::Paused; }
+
+    map.avoid = known_hazards(ecs, &map);
+    let path = rltk::a_star_search(map.xy_idx(player_pos.x, player_pos.y), target_idx, &*map);
+    map.avoid.clear();
+    if path.success && path.steps.len() > 1 { /*The first step is where the player is already standing*/
+        RunState::Traveling { path: path.steps, step: 1, explore: false }
+    } else {
+        RunState::Paused
+    }
+}
+
+/*Aims the player's weapon if it can reach further than the next tile. Firing is picked with the same targeting as scrolls, with the weapon standing in for the item*/
+fn start_firing(ecs: &mut World) -> RunState {
+    let weapon = {
+        let player_entity = ecs.fetch::<Entity>();
+        let equipped = ecs.read_storage::<Equipped>();
+        let bonuses = ecs.read_storage::<MeleePowerBonus>();
+        (&ecs.entities(), &equipped, &bonuses).join()
+            .find(|(_entity, gear, bonus)| gear.owner == *player_entity && bonus.range > 1)
+            .map(|(entity, _gear, bonus)| (entity, bonus.range))
+    };
+    match weapon {
+        None => {
+            ecs.write_resource::<gamelog::GameLog>().add("You have nothing that can reach that far.");
+            RunState::Paused
+        }
+        Some((weapon, _range)) if ecs.read_storage::<Ammo>().get(weapon).is_some_and(|ammo| ammo.count <= 0) => {
+            ecs.write_resource::<gamelog::GameLog>().add("You are out of arrows.");
+            RunState::Paused
+        }
+        Some((weapon, range)) => RunState::ShowTargeting { range, item: weapon, cursor: gui::initial_target(ecs, range) },
+    }
+}
+
+/*Attacks whatever is standing on the picked tile with the wielded weapon, using up a shot if it takes ammunition. Aiming at nothing costs no turn*/
+fn fire_weapon(ecs: &mut World, weapon: Entity, target: Option<rltk::Point>) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let victim = target.and_then(|target| {
+        let map = ecs.fetch::<Map>();
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        map.tile_content[map.xy_idx(target.x, target.y)].iter().copied()
+            .find(|entity| *entity != player_entity && combat_stats.get(*entity).is_some())
+    });
+    match victim {
+        None => {
+            ecs.write_resource::<gamelog::GameLog>().add("There is nothing there to hit.");
+            RunState::Paused
+        }
+        Some(victim) => {
+            if let Some(ammo) = ecs.write_storage::<Ammo>().get_mut(weapon) { ammo.count -= 1; }
+            ecs.write_storage::<WantsToMelee>().insert(player_entity, WantsToMelee { target: victim }).expect("Add target failed");
+            RunState::Running
+        }
+    }
+}
+
+fn item_underfoot(ecs: &World) -> bool {
+    let player_pos = ecs.fetch::<rltk::Point>();
+    let map = ecs.fetch::<Map>();
+    let items = ecs.read_storage::<Item>();
+    map.tile_content[map.xy_idx(player_pos.x, player_pos.y)].iter().any(|entity| items.get(*entity).is_some())
+}
+
+const EXPLORE_SEARCH_DEPTH : f32 = 1000.0; /*Far enough to cross any map*/
+
+/*The way to the nearest unexplored spot, starting with the tile the player is on. The spots are open tiles that have been seen
+and sit next to one that hasn't. Closed doors count as open so exploring goes through them, and known traps are gone round if possible.
+None once there is nowhere left to go*/
+fn explore_path(ecs: &World) -> Option<Vec<usize>> {
+    use rltk::BaseMap;
+    let mut map = ecs.write_resource::<Map>();
+    let player_pos = ecs.fetch::<rltk::Point>();
+    let doors = ecs.read_storage::<Door>();
+    let positions = ecs.read_storage::<Position>();
+    let closed_doors : Vec<usize> = (&doors, &positions).join()
+        .filter(|(door, _pos)| !door.open)
+        .map(|(_door, pos)| map.xy_idx(pos.x, pos.y))
+        .collect();
+    for idx in closed_doors.iter() { map.blocked[*idx] = false; }
+    map.avoid = known_hazards(ecs, &map);
+
+    let start = map.xy_idx(player_pos.x, player_pos.y);
+    let frontier : Vec<usize> = (0..map.tiles.len())
+        .filter(|idx| *idx != start && map.revealed_tiles[*idx] && !map.blocked[*idx] && !map.tiles[*idx].blocks_movement())
+        .filter(|idx| {
+            let (x, y) = map.idx_xy(*idx);
+            (-1..=1).any(|dy| (-1..=1).any(|dx| map.in_bounds(x + dx, y + dy) && !map.revealed_tiles[map.xy_idx(x + dx, y + dy)]))
         })
-        .with(LeftMover{})
-        .build();
+        .collect();
+    let mut path = vec![start];
+    if !frontier.is_empty() {
+        let distances = rltk::DijkstraMap::new(map.width, map.height, &frontier, &*map, EXPLORE_SEARCH_DEPTH);
+        let mut is_frontier = vec![false; map.tiles.len()];
+        for idx in frontier.iter() { is_frontier[*idx] = true; }
+        let distance = |idx: usize| if is_frontier[idx] { 0.0 } else { distances.map[idx] }; /*The search never marks where it started from*/
+        let mut current = start;
+        while distance(current) > 0.0 { /*Downhill all the way to the nearest spot. Stuck on a tile with nothing lower means it can't be reached*/
+            let next = map.get_available_exits(current).iter()
+                .map(|(idx, _cost)| *idx)
+                .min_by(|a, b| distance(*a).partial_cmp(&distance(*b)).unwrap());
+            match next {
+                Some(next) if distance(next) < distance(current) => { path.push(next); current = next; }
+                _ => { path.clear(); break; }
+            }
+        }
     }
 
-    rltk::main_loop(context, gs)/*main_loop comes from the library*/
+    for idx in closed_doors.iter() { map.blocked[*idx] = true; }
+    map.avoid.clear();
+    if path.len() > 1 { Some(path) } else { None }
 }
 
-fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
-    let mut positions = ecs.write_storage::<Position>(); /*Gain write access to the entity's position*/
-    let mut players = ecs.write_storage::<Player>(); /*Gain write access to the entity's player component*/
+/*Sets off exploring, or says why it can't*/
+fn try_start_explore(ecs: &mut World) -> RunState {
+    if monster_in_view(ecs) {
+        ecs.write_resource::<gamelog::GameLog>().add("You can't explore with enemies nearby.");
+        return RunState::Paused;
+    }
+    match explore_path(ecs) {
+        Some(path) => RunState::Traveling { path, step: 1, explore: true },
+        None => {
+            ecs.write_resource::<gamelog::GameLog>().add("There is nothing left here that you can reach.");
+            RunState::Paused
+        }
+    }
+}
 
-    for (_player, pos) in (&mut players, &mut positions).join() { /*Return only entities with player components*/
-        pos.x = min(79 , max(0, pos.x + delta_x)); /*Move relevant entities (only the player) inside the bounds of the screen*/
-        pos.y = min(49, max(0, pos.y + delta_y)); 
+fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    if ctx.left_click { /*Clicking a tile walks there*/
+        let (mouse_x, mouse_y) = ctx.mouse_pos();
+        return match camera::get_screen_bounds(&gs.ecs, ctx).to_world(mouse_x, mouse_y) {
+            Some(target) => try_start_travel(&mut gs.ecs, target),
+            None => RunState::Paused, /*Clicked on the UI, not the map*/
+        };
     }
+    #[cfg(feature = "debug")]
+    if ctx.key == Some(rltk::VirtualKeyCode::Grave) { return RunState::DebugConsole { buffer: String::new() } } /*Not rebindable, it only exists in debug builds*/
+    let action = match ctx.key {/*Match is like a switch in rust. This match matches whether or not any key was pressed*/
+        None => { return RunState::Paused } /*Nothing is pressed, so the world waits*/
+        Some(key) => gs.ecs.fetch::<Keybindings>().action_for(key), /*If something is pressed, look up what that key is bound to*/
+    };
+    let player_action = match action {
+        Some(Action::MoveLeft) => PlayerAction::Move { dx: -1, dy: 0 }, /*When pressing a relevant key, queue a move in that direction*/
+        Some(Action::MoveRight) => PlayerAction::Move { dx: 1, dy: 0 },
+        Some(Action::MoveUp) => PlayerAction::Move { dx: 0, dy: -1 },
+        Some(Action::MoveDown) => PlayerAction::Move { dx: 0, dy: 1 },
+        Some(Action::MoveUpLeft) => PlayerAction::Move { dx: -1, dy: -1 }, /*Diagonals*/
+        Some(Action::MoveUpRight) => PlayerAction::Move { dx: 1, dy: -1 },
+        Some(Action::MoveDownLeft) => PlayerAction::Move { dx: -1, dy: 1 },
+        Some(Action::MoveDownRight) => PlayerAction::Move { dx: 1, dy: 1 },
+        Some(Action::PickUp) => PlayerAction::PickUp, /*Pick up an item*/
+        Some(Action::QuickUse) => { return quick_use(&mut gs.ecs) }
+        Some(Action::Explore) => { return try_start_explore(&mut gs.ecs) }
+        Some(Action::Search) => PlayerAction::Search,
+        Some(Action::Inventory) => { return RunState::ShowInventory } /*Opening the inventory doesn't take a turn*/
+        Some(Action::AssignQuickslot) => { return RunState::ShowQuickslotItem }
+        Some(action @ (Action::Quickslot1 | Action::Quickslot2 | Action::Quickslot3 | Action::Quickslot4 | Action::Quickslot5)) => {
+            return use_quickslot(&mut gs.ecs, action.quickslot().unwrap())
+        }
+        Some(Action::Drop) => { return RunState::ShowDropItem }
+        Some(Action::Throw) => { return RunState::ShowThrowItem }
+        Some(Action::Fire) => { return start_firing(&mut gs.ecs) }
+        Some(Action::Examine) => { return RunState::Examine { cursor: *gs.ecs.fetch::<rltk::Point>() } } /*Starts on the player*/
+        Some(Action::ShowLog) => { return RunState::ShowLog { scroll: 0 } } /*Starts at the newest message*/
+        Some(Action::Menu) => { return RunState::MainMenu { menu_selection: gui::MainMenuSelection::SaveGame } }
+        Some(Action::Descend) => {
+            if try_next_level(&mut gs.ecs) {
+                if badly_wounded(&gs.ecs) { return RunState::Confirm { prompt: "You are badly hurt. Go down anyway?".to_string(), on_yes: ConfirmAction::Descend }; }
+                return RunState::NextLevel;
+            }
+            PlayerAction::Wait /*Not on the stairs, so just wait*/
+        }
+        Some(Action::Ascend) => {
+            if try_previous_level(&mut gs.ecs) { return RunState::PreviousLevel; }
+            return RunState::Paused
+        }
+        Some(Action::Wait) => PlayerAction::Wait,
+        Some(Action::Rest) => { /*Checked once here so a rest that can't start says why, then again before every turn of it*/
+            if let Some(reason) = rest_interruption(&gs.ecs) {
+                gs.ecs.write_resource::<gamelog::GameLog>().add(reason);
+                return RunState::Paused
+            }
+            return RunState::Resting
+        }
+        Some(Action::ToggleThreat) => { /*Only changes what is drawn, so it doesn't take a turn*/
+            gs.show_threat = !gs.show_threat;
+            return RunState::Paused
+        }
+        Some(Action::ToggleMinimap) => {
+            gs.show_minimap = !gs.show_minimap;
+            return RunState::Paused
+        }
+        None => { return RunState::Paused } /*Any other button presses are ignored*/
+    };
+    gs.pending_action = Some(player_action);
+    RunState::Running /*The player asked for something, so the next turn will carry it out*/
 }
 
-fn player_input(gs: &mut State, ctx: &mut Rltk) {
-    match ctx.key {/*Match is like a switch in rust. This match matches whether or not any key was pressed*/
-        None => {} /*Nothing is pressed*/
-        Some(key) => match key { /*If something is pressed, match again on which key was actually pressed*/
-            VirtualKeyCode::Left => try_move_player(-1, 0, &mut gs.ecs), /*When pressing a relevant key, move the entitiy to the relevant position*/
-            VirtualKeyCode::Right => try_move_player(1, 0, &mut gs.ecs), /*Left, right, up, down are by default bound to WASD by the library*/
-            VirtualKeyCode::Up => try_move_player(0, -1, &mut gs.ecs),
-            VirtualKeyCode::Down => try_move_player(0, 1, &mut gs.ecs),
-            _ => {} /*Any other button presses are ignored*/
-        },
+fn perform_action(ecs: &mut World, action: PlayerAction) -> bool { /*False if the action came to nothing, like bumping into a wall, so no turn passes*/
+    match action {
+        PlayerAction::Move { dx, dy } => try_move_player(dx, dy, ecs) == RunState::Running,
+        PlayerAction::Wait => { skip_turn(ecs); true }
+        PlayerAction::PickUp => { get_item(ecs); true }
+        PlayerAction::Search => { search_for_traps(ecs); true }
+        PlayerAction::Wade => true, /*The turn after stepping into shallow water, spent getting through it*/
     }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx : &mut Rltk) {/*Tick is a special function from the rltk library. This function is run once every "tick" or frame*/
         ctx.cls();/*Clear the screen*/
+        self.ecs.write_resource::<game_time::GameTime>().advance(ctx.frame_time_ms); /*Animations move on in fixed steps, not once a frame*/
+        particle_system::cull_dead_particles(&mut self.ecs); /*Particles time out in real time, not in turns*/
+        particle_system::update_floating_text(&mut self.ecs);
+        if self.player_alive() { particle_system::cull_blinking(&mut self.ecs); } /*Needs a map to know what can be seen*/
+        self.ecs.write_resource::<camera::ScreenShake>().update(ctx.frame_time_ms);
 
-        player_input(self, ctx); /*Call the player input function*/
-        self.run_systems();/*Move LeftWalkers to the left on every tick*/
+        let runstate = self.runstate.clone(); /*Remember the state at the start of the frame, so the key that opened a menu isn't also read by that menu*/
+        match runstate {
+            RunState::Running => {
+                self.run_systems(); /*Run a turn, which sets the state back to paused*/
+                if !self.player_alive() { /*Dying shows how the run went before going back to the main menu*/
+                    self.runstate = RunState::GameOver;
+                }
+            }
+            RunState::Paused => self.runstate = player_input(self, ctx), /*Wait for the player to do something*/
+            RunState::ShowInventory | RunState::ShowDropItem | RunState::ShowThrowItem | RunState::ShowQuickslotItem | RunState::AssignQuickslot { .. } | RunState::ShowTargeting { .. } | RunState::Examine { .. } | RunState::ShowLog { .. } | RunState::NameEntry { .. } | RunState::MainMenu { .. } | RunState::Confirm { .. } | RunState::GameOver => {} /*Menus are handled after drawing, so they end up on top of the map*/
+            #[cfg(feature = "debug")]
+            RunState::DebugConsole { .. } => {}
+            RunState::NextLevel => {
+                self.goto_level(1);
+                self.runstate = RunState::Running; /*Run a turn so the player can see the new level straight away*/
+            }
+            RunState::PreviousLevel => {
+                self.goto_level(-1);
+                self.runstate = RunState::Running;
+            }
+            RunState::Traveling { ref path, step, explore } => self.runstate = self.travel_step(path, step, explore),
+            RunState::Resting => self.runstate = self.rest_step(),
+            RunState::MagicMapReveal { row } => { /*One row of the map a frame, top to bottom*/
+                let mut map = self.ecs.write_resource::<Map>();
+                for x in 0..map.width {
+                    let idx = map.xy_idx(x, row);
+                    map.revealed_tiles[idx] = true;
+                }
+                self.runstate = if row + 1 >= map.height { RunState::Paused } else { RunState::MagicMapReveal { row: row + 1 } };
+            }
+        }
 
-        let positions = self.ecs.read_storage::<Position>(); /*Gain read only access from the ECS to the container used to store position components*/
-        let renderables = self.ecs.read_storage::<Renderable>(); /*Same for renderables*/
+        let in_game = self.player_alive();
+        if in_game { /*Before a game has started there is nothing to draw but the menu*/
+            camera::render_camera(&self.ecs, ctx, self.show_threat); /*The map and everything on it, scrolled to follow the player*/
+            if self.show_minimap { gui::draw_minimap(&self.ecs, ctx); } /*Before the UI, so tooltips still go over it*/
+            gui::draw_ui(&self.ecs, ctx); /*The UI goes on last, in its own panel below the map*/
+        }
+
+        if runstate == RunState::ShowInventory {
+            let (result, item) = gui::show_inventory(&self.ecs, ctx);
+            match result {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::Paused,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected => self.runstate = use_from_pack(&mut self.ecs, item.unwrap()),
+            }
+        }
+
+        if runstate == RunState::ShowQuickslotItem {
+            let (result, item) = gui::quickslot_item_menu(&self.ecs, ctx);
+            match result {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::Paused,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected => self.runstate = RunState::AssignQuickslot { item: item.unwrap() },
+            }
+        }
+
+        if let RunState::AssignQuickslot { item } = runstate {
+            let (result, slot) = gui::quickslot_prompt(&self.ecs, ctx, item);
+            match result {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::Paused,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected => { /*Filling a slot is free, like opening the inventory*/
+                    let slot = slot.unwrap();
+                    self.ecs.fetch_mut::<hotbar::Hotbar>().assign(&self.ecs, slot, item);
+                    self.ecs.write_resource::<gamelog::GameLog>().add(format!("That goes on key {}.", slot));
+                    self.runstate = RunState::Paused;
+                }
+            }
+        }
+
+        if runstate == RunState::ShowThrowItem {
+            let (result, item) = gui::throw_item_menu(&self.ecs, ctx);
+            match result {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::Paused,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected => { /*Thrown things are aimed the same way as scrolls, and the target tells the item system it was thrown*/
+                    let item = item.unwrap();
+                    let range = inventory_system::throw_range(&self.ecs, item);
+                    let cursor = gui::initial_target(&self.ecs, range);
+                    self.runstate = RunState::ShowTargeting { range, item, cursor };
+                }
+            }
+        }
+
+        if let RunState::ShowTargeting { range, item, mut cursor } = runstate {
+            let (result, target) = gui::ranged_target(&self.ecs, ctx, range, item, &mut cursor);
+            match result {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::Paused, /*Changed their mind, so no turn is used*/
+                gui::ItemMenuResult::NoResponse => self.runstate = RunState::ShowTargeting { range, item, cursor }, /*Keep wherever the cursor was moved to*/
+                gui::ItemMenuResult::Selected if self.ecs.read_storage::<Equipped>().get(item).is_some() => { /*Aiming a wielded weapon, not using an item*/
+                    self.runstate = fire_weapon(&mut self.ecs, item, target);
+                }
+                gui::ItemMenuResult::Selected => {
+                    let player_entity = *self.ecs.fetch::<Entity>();
+                    let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                    intent.insert(player_entity, WantsToUseItem { item, target }).expect("Unable to insert intent");
+                    self.runstate = RunState::Running;
+                }
+            }
+        }
+
+        if let RunState::Examine { mut cursor } = runstate {
+            match gui::examine(&self.ecs, ctx, &mut cursor) {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::Paused, /*Looking never takes a turn*/
+                _ => self.runstate = RunState::Examine { cursor },
+            }
+        }
 
-        for (pos, render) in (&positions, &renderables).join() { /*A for loop that loops over all entities that have the relevant traits, in this case those that have a position and are renderable*/
-            ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph); /*Sets these properties of relevant entities to be rendered on the screen*/
+        if let RunState::NameEntry { ref buffer } = runstate {
+            let mut buffer = buffer.clone();
+            match gui::text_entry(ctx, "What is your name?", &mut buffer, stats::MAX_NAME_LENGTH) {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame },
+                gui::ItemMenuResult::NoResponse => self.runstate = RunState::NameEntry { buffer },
+                gui::ItemMenuResult::Selected => {
+                    self.new_game();
+                    self.ecs.insert(stats::PlayerName::new(&buffer)); /*A blank name becomes Adventurer*/
+                    self.runstate = RunState::Running; /*Run one turn straight away so the player's field of view is calculated before the first key press*/
+                }
+            }
+        }
+
+        if let RunState::MainMenu { menu_selection } = runstate {
+            match gui::main_menu(menu_selection, in_game, &self.save_slots, self.save_slot, self.difficulty, self.menu_message.as_deref(), ctx) {
+                gui::MainMenuResult::Cancel => if in_game { self.runstate = RunState::Paused; self.menu_message = None; }, /*Back to the game, if there is one*/
+                gui::MainMenuResult::NoSelection { selected } => self.runstate = RunState::MainMenu { menu_selection: selected },
+                gui::MainMenuResult::DeleteSlot { slot } => self.runstate = RunState::Confirm { prompt: format!("Delete the save in slot {}?", slot + 1), on_yes: ConfirmAction::DeleteSlot { slot } },
+                gui::MainMenuResult::Selected { selected } => match selected {
+                    gui::MainMenuSelection::SaveSlot(slot) => {
+                        self.save_slot = slot;
+                        self.menu_message = None;
+                    }
+                    gui::MainMenuSelection::Difficulty => {
+                        self.difficulty = self.difficulty.next();
+                        self.runstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::Difficulty };
+                    }
+                    gui::MainMenuSelection::NewGame => { /*Name the character first, the game is made once they have*/
+                        self.menu_message = None;
+                        self.runstate = RunState::NameEntry { buffer: String::new() };
+                    }
+                    gui::MainMenuSelection::SaveGame => {
+                        saveload_system::save_game(&mut self.ecs, self.save_slot);
+                        self.save_slots[self.save_slot as usize] = saveload_system::slot_summary(self.save_slot);
+                        self.ecs.fetch_mut::<gamelog::GameLog>().add("Game saved.");
+                        self.runstate = RunState::Paused;
+                    }
+                    gui::MainMenuSelection::LoadGame => {
+                        if saveload_system::does_save_exist(self.save_slot) {
+                            match saveload_system::load_game(&mut self.ecs, self.save_slot) {
+                                Ok(()) => {
+                                    let mut mapindex = MapIndexingSystem{}; /*The loaded map doesn't know what is standing where yet*/
+                                    mapindex.run_now(&self.ecs);
+                                    self.ecs.write_resource::<gamelog::GameLog>().add("Game loaded."); /*Under the messages from before the save*/
+                                    self.ecs.insert(hotbar::Hotbar::default()); /*The slots pointed at entities from before the load*/
+                                    self.menu_message = None;
+                                    self.runstate = RunState::Paused;
+                                }
+                                Err(e) => self.menu_message = Some(e.to_string()), /*Stay on the menu and say what went wrong*/
+                            }
+                        }
+                    }
+                    gui::MainMenuSelection::Quit => ctx.quit(),
+                }
+            }
+        }
+
+        #[cfg(feature = "debug")]
+        if let RunState::DebugConsole { ref buffer } = runstate {
+            let mut buffer = buffer.clone();
+            match gui::text_entry(ctx, "Debug command", &mut buffer, debug_console::MAX_COMMAND_LENGTH) {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::Paused,
+                gui::ItemMenuResult::NoResponse => self.runstate = RunState::DebugConsole { buffer },
+                gui::ItemMenuResult::Selected => self.runstate = debug_console::run_command(&mut self.ecs, &buffer), /*Commands don't take a turn*/
+            }
+        }
+
+        if let RunState::Confirm { ref prompt, ref on_yes } = runstate {
+            match gui::confirm(prompt, ctx) {
+                gui::ConfirmResult::NoResponse => {}
+                gui::ConfirmResult::Yes => self.runstate = self.confirmed(on_yes),
+                gui::ConfirmResult::No => self.runstate = on_yes.declined(),
+            }
+        }
+
+        if runstate == RunState::GameOver {
+            match gui::game_over(&self.ecs, ctx) {
+                gui::GameOverResult::NoSelection => {}
+                gui::GameOverResult::ShowLog => self.runstate = RunState::ShowLog { scroll: 0 }, /*See what killed you*/
+                gui::GameOverResult::QuitToMenu => {
+                    self.ecs.delete_all(); /*The run is over, nothing from it should carry into the next one*/
+                    self.ecs.remove::<Entity>(); /*Without a player the menu knows there is no game to go back to*/
+                    self.runstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame };
+                }
+            }
+        }
+
+        if let RunState::ShowLog { mut scroll } = runstate {
+            match gui::show_log(&self.ecs, ctx, &mut scroll) {
+                gui::ItemMenuResult::Cancel => self.runstate = if self.player_alive() { RunState::Paused } else { RunState::GameOver }, /*Back to wherever it was opened from*/
+                _ => self.runstate = RunState::ShowLog { scroll },
+            }
+        }
+
+        if runstate == RunState::ShowDropItem {
+            let (result, item) = gui::drop_item_menu(&self.ecs, ctx);
+            match result {
+                gui::ItemMenuResult::Cancel => self.runstate = RunState::Paused,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected => { /*Dropping takes a turn too*/
+                    let player_entity = *self.ecs.fetch::<Entity>();
+                    let mut intent = self.ecs.write_storage::<WantsToDropItem>();
+                    intent.insert(player_entity, WantsToDropItem { item: item.unwrap() }).expect("Unable to insert intent");
+                    self.runstate = RunState::Running;
+                }
+            }
         }
     }
 }
@@ -125,21 +860,407 @@ impl GameState for State {
 /*Rust lifetimes are used but i barely understand them*/
 impl<'a> System<'a> for LeftWalker { /*This implements logic for LeftWalker entities, which in this case is to move left at all times*/
     type SystemData = (ReadStorage<'a, LeftMover>, 
-                        WriteStorage<'a, Position>);/*Gain access to their properties*/
+                        WriteStorage<'a, Position>,/*Gain access to their properties*/
+                        ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteExpect<'a, movement::MovedChannel>);
 
-    fn run(&mut self, (lefty, mut pos) : Self::SystemData) {
-        for (_lefty,pos) in (&lefty, &mut pos).join() { /*For any of the entities that has the relevant traits (is a LeftWalker and has a position), do X*/
+    fn run(&mut self, (lefty, mut pos, map, entities, mut moved) : Self::SystemData) {
+        for (entity, _lefty, pos) in (&entities, &lefty, &mut pos).join() { /*For any of the entities that has the relevant traits (is a LeftWalker and has a position), do X*/
             pos.x -= 1;
-            if pos.x < 0 { pos.x = 79; } /*X in this case is move them to the left, wrapping around if they hit the wall*/
+            if !map.in_bounds(pos.x, pos.y) { pos.x = map.width - 1; } /*X in this case is move them to the left, wrapping around if they hit the wall*/
+            moved.single_write(movement::MovedEvent { entity, x: pos.x, y: pos.y });
         }
     }
 }
 
 
 impl State {
+    fn new(settings: settings::Settings, keybindings: Keybindings, raws: raws::RawMaster, seed: u64) -> State { /*Everything the game needs before the main menu, with no dungeon yet*/
+        let mut gs = State {
+            ecs: World::new(), /*gs is the GameState. It instantiates a new world*/
+            runstate: RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame }, /*Start on the main menu, the dungeon is made when New Game is picked*/
+            show_threat: true,
+            show_minimap: true,
+            pending_action: None,
+            menu_message: None,
+            save_slot: 0,
+            save_slots: saveload_system::slot_summaries(),
+            difficulty: difficulty::Difficulty::default(),
+        };
+        saveload_system::register_components(&mut gs.ecs); /*Register all the components that an entity can have. The list lives next to the save code so the two stay in step*/
+        gs.ecs.insert(particle_system::ParticleBuilder::new());
+        gs.ecs.insert(camera::ScreenShake::new());
+        gs.ecs.insert(game_time::GameTime::default());
+        gs.ecs.insert(WallBumps { consecutive: 0 });
+        gs.ecs.insert(damage_system::GodMode::default());
+        gs.ecs.insert(inventory_system::MagicMapping::default());
+        gs.ecs.insert(dungeon::IdentifiedItems::default());
+        gs.ecs.insert(factions::FactionTable::default());
+        gs.ecs.insert(difficulty::Difficulty::default());
+        gs.ecs.insert(deletion_queue::ToDelete::default());
+        gs.ecs.insert(stats::PlayerName::default());
+        gs.ecs.insert(hotbar::Hotbar::default());
+        movement::register(&mut gs.ecs);
+        gs.ecs.insert(settings);
+        gs.ecs.insert(keybindings);
+        gs.ecs.insert(raws);
+        gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new()); /*Hands out the ids that entities are saved under*/
+        gs.ecs.insert(SimpleMarkerAllocator::<SerializeLevel>::new());
+        gs.ecs.insert(rltk::RandomNumberGenerator::seeded(seed)); /*Everything random comes from this one generator, so the same seed always gives the same game*/
+        gs
+    }
+
     fn run_systems(&mut self) { /*The function can mutate itself*/
+        let mut wading = false;
+        if let Some(action) = self.pending_action.take() { /*At most one queued action per turn, and it goes before anything else moves*/
+            if !perform_action(&mut self.ecs, action) {
+                self.runstate = RunState::Paused;
+                return;
+            }
+            wading = matches!(action, PlayerAction::Move { .. }) && {
+                let map = self.ecs.fetch::<Map>();
+                let player_pos = self.ecs.fetch::<rltk::Point>();
+                map.tiles[map.xy_idx(player_pos.x, player_pos.y)] == TileType::ShallowWater
+            };
+        }
         let mut lw = LeftWalker{}; /*Create instance of the LeftWalker struct*/
         lw.run_now(&self.ecs); /*Makes entities with the LeftWalker component run left with a call to the ECS*/
+        let mut energy = EnergySystem{}; /*The player's action gives everything else a turn's worth of energy to spend*/
+        energy.run_now(&self.ecs);
+        let mut vis = VisibilitySystem{}; /*Recalculate what everything with a viewshed can see*/
+        vis.run_now(&self.ecs);
+        let mut mob = MonsterAI{ round: 0 }; /*Let the monsters decide what to do*/
+        mob.run_now(&self.ecs);
+        let mut mapindex = MapIndexingSystem{}; /*Work out what is standing where now that everything has moved*/
+        mapindex.run_now(&self.ecs);
+        let mut triggers = TriggerSystem{}; /*Set off any traps that got stepped on, now that we know who is standing where*/
+        triggers.run_now(&self.ecs);
+        let mut pickup = ItemCollectionSystem{}; /*Move picked up items into backpacks*/
+        pickup.run_now(&self.ecs);
+        let mut items = ItemUseSystem{}; /*Apply the effects of used items*/
+        items.run_now(&self.ecs);
+        let mut drop_items = ItemDropSystem{}; /*Put dropped items back on the map*/
+        drop_items.run_now(&self.ecs);
+        let mut hunger = HungerSystem{}; /*Tick the player's stomach, which can hurt, so it goes before damage*/
+        hunger.run_now(&self.ecs);
+        let mut poison = DamageOverTimeSystem{}; /*Same again for anything poisoned*/
+        poison.run_now(&self.ecs);
+        let mut melee = MeleeCombatSystem{}; /*Turn attack intents into damage*/
+        melee.run_now(&self.ecs);
+        let mut terrain = TerrainSystem{}; /*Burn whatever has ended up in lava, after knockbacks have had their say*/
+        terrain.run_now(&self.ecs);
+        let mut damage = DamageSystem{}; /*Apply the damage, which comes after items so scrolls hurt on the turn they are used*/
+        damage.run_now(&self.ecs);
+        let mut dead = DeadCleanupSystem{}; /*Anything that died this turn is queued up for removal*/
+        dead.run_now(&self.ecs);
+        for round in 1..energy_system::MAX_ACTIONS_PER_TURN { /*Fast monsters still have energy left, so they go again*/
+            if !energy_system::anyone_ready(&self.ecs) { break; }
+            self.run_extra_round(round);
+        }
+        let mut particles = particle_system::ParticleSpawnSystem{}; /*Create the particles the other systems asked for*/
+        particles.run_now(&self.ecs);
+        deletion_queue::delete_queued(&mut self.ecs); /*Everything the systems are finished with goes, now that nothing is iterating*/
         self.ecs.maintain(); /*If actions are queued up, execute them*/
+        self.ecs.write_resource::<stats::Stats>().turns += 1;
+        if wading && self.player_alive() { /*Wading costs the player their next turn too, and the world carries on without them*/
+            self.pending_action = Some(PlayerAction::Wade);
+            self.run_systems();
+            return;
+        }
+        let reveal = std::mem::take(&mut self.ecs.write_resource::<inventory_system::MagicMapping>().pending);
+        self.runstate = if reveal { RunState::MagicMapReveal { row: 0 } } else { RunState::Paused }; /*The turn is over, wait for the player again*/
+    }
+
+    /*Another go for whatever is fast enough to act more than once a turn. Only the monster side of a turn, the player already had theirs*/
+    fn run_extra_round(&mut self, round: usize) {
+        let mut vis = VisibilitySystem{};
+        vis.run_now(&self.ecs);
+        let mut mob = MonsterAI{ round };
+        mob.run_now(&self.ecs);
+        let mut mapindex = MapIndexingSystem{};
+        mapindex.run_now(&self.ecs);
+        let mut triggers = TriggerSystem{};
+        triggers.run_now(&self.ecs);
+        let mut melee = MeleeCombatSystem{};
+        melee.run_now(&self.ecs);
+        let mut damage = DamageSystem{};
+        damage.run_now(&self.ecs);
+        let mut dead = DeadCleanupSystem{};
+        dead.run_now(&self.ecs);
+    }
+
+    /*Takes one step along a travel path and runs the turn. Travel stops at the end of the path, when something gets in the way,
+    or as soon as a monster comes into view. Exploring picks up whatever it walks over, and at the end of each path heads for the next unexplored spot.
+    A path only crosses a known trap when there was no other way, and then it stops first and asks*/
+    fn travel_step(&mut self, path: &[usize], step: usize, explore: bool) -> RunState {
+        if monster_in_view(&self.ecs) { return RunState::Paused; }
+        if explore && item_underfoot(&self.ecs) { /*One item a turn, then back here to see if there is another*/
+            self.pending_action = Some(PlayerAction::PickUp);
+            self.run_systems();
+            return if self.player_alive() { RunState::Traveling { path: path.to_vec(), step, explore } } else { RunState::GameOver };
+        }
+        let hazard_next = known_hazards(&self.ecs, &self.ecs.fetch::<Map>()).contains(&path[step]);
+        if hazard_next {
+            return RunState::Confirm { prompt: hazard_prompt(&self.ecs, path[step]), on_yes: ConfirmAction::StepOnHazard { path: path.to_vec(), step, explore } };
+        }
+        self.travel_move(path, step, explore)
+    }
+
+    fn confirmed(&mut self, action: &ConfirmAction) -> RunState { /*Carries out whatever the player just said yes to*/
+        match action {
+            ConfirmAction::Descend => RunState::NextLevel,
+            ConfirmAction::DeleteSlot { slot } => {
+                match saveload_system::delete_save(*slot) {
+                    Ok(()) => {
+                        self.save_slots[*slot as usize] = None;
+                        self.menu_message = None;
+                    }
+                    Err(e) => self.menu_message = Some(format!("Unable to delete the save: {}", e)), /*The slot keeps showing what is still in it*/
+                }
+                RunState::MainMenu { menu_selection: gui::MainMenuSelection::SaveSlot(*slot) }
+            }
+            ConfirmAction::StepOnHazard { path, step, explore } => self.travel_move(path, *step, *explore),
+        }
     }
-}
\ No newline at end of file
+
+    fn travel_move(&mut self, path: &[usize], step: usize, explore: bool) -> RunState { /*The step itself, once travel_step has decided to take it*/
+        let (dx, dy) = {
+            let map = self.ecs.fetch::<Map>();
+            let player_pos = self.ecs.fetch::<rltk::Point>();
+            let (next_x, next_y) = map.idx_xy(path[step]);
+            (next_x - player_pos.x, next_y - player_pos.y)
+        };
+        let turns_before = self.ecs.fetch::<stats::Stats>().turns;
+        self.pending_action = Some(PlayerAction::Move { dx, dy });
+        self.run_systems();
+        if !self.player_alive() { return RunState::GameOver; }
+        let arrived = { /*If the step didn't land on the next tile, something moved into the way*/
+            let map = self.ecs.fetch::<Map>();
+            let player_pos = self.ecs.fetch::<rltk::Point>();
+            map.xy_idx(player_pos.x, player_pos.y) == path[step]
+        };
+        let took_turn = self.ecs.fetch::<stats::Stats>().turns != turns_before; /*Like opening a door that was in the way, which exploring can carry on from*/
+        if explore && (arrived && step + 1 >= path.len() || !arrived && took_turn) { return try_start_explore(&mut self.ecs); }
+        if !arrived { return RunState::Paused; }
+        if step + 1 >= path.len() { return RunState::Paused; } /*Arrived*/
+        RunState::Traveling { path: path.to_vec(), step: step + 1, explore }
+    }
+
+    /*Waits one turn, the same as pressing wait, then carries on resting next frame unless something has come up*/
+    fn rest_step(&mut self) -> RunState {
+        if let Some(reason) = rest_interruption(&self.ecs) {
+            self.ecs.write_resource::<gamelog::GameLog>().add(reason);
+            return RunState::Paused;
+        }
+        self.pending_action = Some(PlayerAction::Wait);
+        self.run_systems();
+        if !self.player_alive() { return RunState::GameOver; }
+        RunState::Resting
+    }
+
+    fn player_alive(&self) -> bool { /*True while there is a game going, false before the first game starts and once the player has died*/
+        match self.ecs.try_fetch::<Entity>() {
+            None => false,
+            Some(player_entity) => self.ecs.read_storage::<CombatStats>().get(*player_entity).is_some_and(|stats| stats.hp > 0),
+        }
+    }
+
+    fn new_game(&mut self) { /*Throw away whatever was there and build a fresh dungeon*/
+        self.ecs.delete_all();
+        self.ecs.insert(self.difficulty); /*Before the player and the monsters are made, since it changes their stats*/
+
+        let (width, height) = self.ecs.fetch::<settings::Settings>().map_size();
+        let (map, rooms) = new_map(&mut self.ecs.write_resource::<rltk::RandomNumberGenerator>(), 1, width, height);
+        let (player_x, player_y) = rooms[0].center(); /*The player starts in the middle of the first room*/
+        self.ecs.insert(map); /*The map is a resource, meaning any system can ask the ECS for it*/
+
+        let player_entity = spawner::player(&mut self.ecs, player_x, player_y);
+        self.ecs.insert(player_entity); /*Monsters need to know who the player is and where they are standing*/
+        self.ecs.insert(rltk::Point::new(player_x, player_y));
+
+        let mut dungeon = dungeon::MasterDungeon::default(); /*Before anything spawns, so the items get their made up names*/
+        {
+            let raws = self.ecs.fetch::<raws::RawMaster>();
+            dungeon.obfuscate_names(&mut self.ecs.write_resource::<rltk::RandomNumberGenerator>(), &raws.disguised(raws::Disguise::Potion), &raws.disguised(raws::Disguise::Scroll));
+        }
+        self.ecs.insert(dungeon);
+        self.ecs.insert(dungeon::IdentifiedItems::default());
+
+        spawner::populate_level(&mut self.ecs, &rooms);
+
+        self.ecs.insert(gamelog::GameLog::new("Welcome to KazooGame"));
+        self.ecs.insert(stats::Stats::default()); /*A new run starts from zero*/
+        self.ecs.insert(hotbar::Hotbar::default());
+    }
+
+    fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> { /*Everything except the player and whatever they are carrying or wearing*/
+        let entities = self.ecs.entities();
+        let backpack = self.ecs.read_storage::<InBackpack>();
+        let equipped = self.ecs.read_storage::<Equipped>();
+        let player_entity = self.ecs.fetch::<Entity>();
+
+        entities.join()
+            .filter(|entity| *entity != *player_entity)
+            .filter(|entity| backpack.get(*entity).is_none_or(|pack| pack.owner != *player_entity))
+            .filter(|entity| equipped.get(*entity).is_none_or(|gear| gear.owner != *player_entity))
+            .collect()
+    }
+
+    /*Fills up every wand the player is carrying or holding. Done on reaching a level for the first time, so going up and down stairs doesn't count*/
+    fn recharge_wands(&mut self) {
+        let player_entity = *self.ecs.fetch::<Entity>();
+        let backpack = self.ecs.read_storage::<InBackpack>();
+        let equipped = self.ecs.read_storage::<Equipped>();
+        let mut charges = self.ecs.write_storage::<Charges>();
+        let mut recharged = false;
+        for (entity, charges) in (&self.ecs.entities(), &mut charges).join() {
+            let carried = backpack.get(entity).is_some_and(|pack| pack.owner == player_entity) || equipped.get(entity).is_some_and(|e| e.owner == player_entity);
+            if carried && charges.current < charges.max {
+                charges.current = charges.max;
+                recharged = true;
+            }
+        }
+        if recharged { self.ecs.write_resource::<gamelog::GameLog>().add("Your wands hum with fresh power."); }
+    }
+
+    /*Moves the player delta levels down (or up, if negative). The level being left is put away in the dungeon cache with everything on it,
+    and the level being entered is taken back out of the cache if it has been visited before*/
+    fn goto_level(&mut self, delta: i32) {
+        let to_remove = self.entities_to_remove_on_level_change();
+        let snapshot = saveload_system::serialize_level(&mut self.ecs, &to_remove);
+        let old_map = (*self.ecs.fetch::<Map>()).clone();
+        let current_depth = old_map.depth;
+        self.ecs.write_resource::<dungeon::MasterDungeon>().store_level(old_map, snapshot);
+
+        for target in to_remove { /*Clear out the old level*/
+            self.ecs.delete_entity(target).expect("Unable to delete entity");
+        }
+
+        let target_depth = current_depth + delta;
+        let cached = self.ecs.write_resource::<dungeon::MasterDungeon>().take_level(target_depth);
+        let first_visit = cached.is_none();
+        match cached {
+            Some(level) => {
+                let mut map = level.map;
+                map.tile_content = vec![Vec::new(); map.tiles.len()]; /*Not cached, the indexing system fills it back in*/
+                map.indexed = None;
+                map.lit_by = None; /*Both are worked out again for the entities that come back with the level*/
+                *self.ecs.write_resource::<Map>() = map;
+                saveload_system::deserialize_level(&mut self.ecs, &level.entities);
+            }
+            None => {
+                let (width, height) = self.ecs.fetch::<settings::Settings>().map_size();
+                let (map, rooms) = new_map(&mut self.ecs.write_resource::<rltk::RandomNumberGenerator>(), target_depth, width, height);
+                *self.ecs.write_resource::<Map>() = map;
+                spawner::populate_level(&mut self.ecs, &rooms);
+            }
+        }
+
+        let arrival = if delta > 0 { TileType::UpStairs } else { TileType::DownStairs }; /*Going down you arrive on the way back up, and the other way round*/
+        let (player_x, player_y) = {
+            let map = self.ecs.fetch::<Map>();
+            let idx = map.tiles.iter().position(|tile| *tile == arrival).expect("Level has no stairs to arrive on");
+            map.idx_xy(idx)
+        };
+        {
+            let mut player_position = self.ecs.write_resource::<rltk::Point>();
+            *player_position = rltk::Point::new(player_x, player_y);
+            let player_entity = *self.ecs.fetch::<Entity>();
+            let mut positions = self.ecs.write_storage::<Position>();
+            if let Some(pos) = positions.get_mut(player_entity) {
+                pos.x = player_x;
+                pos.y = player_y;
+            }
+            let mut viewsheds = self.ecs.write_storage::<Viewshed>(); /*Everything they could see belonged to the old level*/
+            if let Some(vs) = viewsheds.get_mut(player_entity) {
+                vs.dirty = true;
+            }
+        }
+
+        self.ecs.fetch_mut::<gamelog::GameLog>().add(if delta > 0 { "You descend to the next level." } else { "You climb back up the stairs." });
+        if first_visit { self.recharge_wands(); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started(seed: u64) -> State {
+        let mut gs = State::new(settings::Settings::defaults(), Keybindings::defaults(), raws::RawMaster::built_in(), seed);
+        gs.new_game();
+        gs
+    }
+
+    fn take_turn(gs: &mut State, action: PlayerAction) {
+        gs.pending_action = Some(action);
+        gs.run_systems();
+    }
+
+    fn attack_nearest(gs: &mut State) { /*The player swings at the closest monster, wherever it is, so there is always some fighting to compare*/
+        let player = *gs.ecs.fetch::<Entity>();
+        let target = {
+            let player_pos = *gs.ecs.fetch::<rltk::Point>();
+            let (entities, monsters, positions, stats) = (gs.ecs.entities(), gs.ecs.read_storage::<Monster>(), gs.ecs.read_storage::<Position>(), gs.ecs.read_storage::<CombatStats>());
+            (&entities, &monsters, &positions, &stats).join()
+                .filter(|(_entity, _monster, _pos, stats)| stats.hp > 0)
+                .min_by_key(|(_entity, _monster, pos, _stats)| ((pos.x - player_pos.x).pow(2) + (pos.y - player_pos.y).pow(2), pos.x, pos.y))
+                .map(|(entity, _monster, _pos, _stats)| entity)
+        };
+        if let Some(target) = target {
+            gs.ecs.write_storage::<WantsToMelee>().insert(player, WantsToMelee { target }).expect("Unable to insert");
+        }
+        take_turn(gs, PlayerAction::Wait);
+    }
+
+    fn everyone(gs: &State) -> Vec<(i32, i32, i32, i32)> { /*Where everything that can fight is and how hurt it is, in an order that doesn't depend on entity ids*/
+        let (positions, stats) = (gs.ecs.read_storage::<Position>(), gs.ecs.read_storage::<CombatStats>());
+        let mut everyone : Vec<(i32, i32, i32, i32)> = (&positions, &stats).join().map(|(pos, stats)| (pos.x, pos.y, stats.hp, stats.max_hp)).collect();
+        everyone.sort();
+        everyone
+    }
+
+    #[test]
+    fn a_loaded_game_plays_on_the_same_as_one_never_saved() {
+        let mut uninterrupted = started(42);
+        let mut saved = started(42);
+        for gs in [&mut uninterrupted, &mut saved] {
+            for _turn in 0..5 { take_turn(gs, PlayerAction::Wait); } /*Monsters wander, so the generator is part way along when the game is saved*/
+        }
+
+        let mut file = Vec::new();
+        saveload_system::save_to(&mut saved.ecs, &mut file);
+        let mut loaded = State::new(settings::Settings::defaults(), Keybindings::defaults(), raws::RawMaster::built_in(), 999); /*A different seed, so only the saved generator can give the same game*/
+        assert!(saveload_system::load_from(&mut loaded.ecs, std::str::from_utf8(&file).expect("Save is not utf8")).is_ok());
+        MapIndexingSystem{}.run_now(&loaded.ecs);
+        assert_eq!(everyone(&loaded), everyone(&uninterrupted));
+
+        for gs in [&mut uninterrupted, &mut loaded] {
+            gs.goto_level(1);
+            take_turn(gs, PlayerAction::Wait);
+            for _turn in 0..10 { attack_nearest(gs); }
+        }
+        assert_eq!(loaded.ecs.fetch::<Map>().depth, 2);
+        assert!(loaded.ecs.fetch::<Map>().tiles == uninterrupted.ecs.fetch::<Map>().tiles, "the next level was dug differently");
+        let after = everyone(&uninterrupted);
+        assert!(after.iter().any(|(_x, _y, hp, max_hp)| hp < max_hp), "nobody got hurt, so there were no hits to compare");
+        assert_eq!(everyone(&loaded), after);
+    }
+
+    #[test]
+    fn walking_into_lava_over_and_over_is_only_mentioned_now_and_then() {
+        let mut gs = started(42);
+        {
+            let player_pos = *gs.ecs.fetch::<rltk::Point>();
+            let mut map = gs.ecs.write_resource::<Map>();
+            let idx = map.xy_idx(player_pos.x + 1, player_pos.y);
+            map.tiles[idx] = TileType::Lava;
+        }
+        let logged_before = gs.ecs.fetch::<gamelog::GameLog>().entries.len();
+        for _press in 0..BUMP_MESSAGE_EVERY * 2 {
+            assert!(try_move_player(1, 0, &mut gs.ecs) == RunState::Paused);
+        }
+        assert_eq!(gs.ecs.fetch::<gamelog::GameLog>().entries.len() - logged_before, 2); /*The first press, then once more*/
+    }
+}
@@ -0,0 +1,496 @@
+use rltk::{RandomNumberGenerator, BaseMap, Algorithm2D, Point, SmallVec, RGB};
+use specs::prelude::*;
+use super::{Rect, map_theme::{Theme, theme_for_depth}};
+use std::cmp::{max, min};
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+
+/*Every tile on the map is one of these. PartialEq lets us compare tiles with ==, Copy/Clone lets us pass them around by value*/
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum TileType {
+    Wall,
+    Floor,
+    DownStairs,
+    UpStairs,
+    ShallowWater, /*Can be waded through, but it takes an extra turn*/
+    DeepWater, /*Nothing in the dungeon can swim yet, so this is as good as a wall that can be seen over*/
+    Lava, /*Burns whatever ends its turn in it. Nothing walks in on purpose, but things can be knocked in*/
+}
+
+impl TileType {
+    pub fn blocks_movement(self) -> bool { /*Tiles nothing can step onto, whatever is or isn't standing there*/
+        matches!(self, TileType::Wall | TileType::DeepWater | TileType::Lava)
+    }
+}
+
+const POOL_CHANCE : i32 = 6; /*About one room in this many gets a pool of water, or lava deeper down*/
+const LAVA_DEPTH : i32 = 3; /*The first level lava can turn up on*/
+const AVOID_COST : f32 = 100.0; /*Enough that a route will go a long way round rather than over a tile in Map::avoid*/
+
+/*How big a level is, unless the window is bigger still (see Settings::map_size). The camera scrolls to follow the player*/
+pub const MAPWIDTH: i32 = 120;
+pub const MAPHEIGHT: i32 = 80;
+
+/*The map is stored as a resource in the ECS rather than as an entity, since there is only ever one of them*/
+#[derive(Default, Serialize, Deserialize, Clone)]
+#[serde(default)] /*Fields missing from an older save are left at their defaults*/
+pub struct Map {
+    #[serde(with = "rle_tiles")]
+    pub tiles: Vec<TileType>, /*Saved as runs of the same tile, since most of the map is long stretches of wall*/
+    pub width: i32,
+    pub height: i32,
+    #[serde(with = "packed_bools")]
+    pub revealed_tiles: Vec<bool>, /*Tiles the player has seen at some point. Saved 8 to a byte*/
+    pub visible_tiles: Vec<bool>, /*Tiles the player can see right now*/
+    pub blocked: Vec<bool>, /*Tiles that can't be walked onto, either because of a wall or something standing there*/
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub tile_content: Vec<Vec<Entity>>, /*Every entity standing on each tile. Not saved, the indexing system rebuilds it*/
+    pub depth: i32, /*How far down the dungeon this level is, starting at 1*/
+    pub bloodstains: HashSet<usize>, /*Tiles where something has been hurt. They belong to the level, so a new level starts clean*/
+    #[serde(skip)]
+    pub view_blocked: HashSet<usize>, /*Tiles with something on them that blocks sight, like a closed door. Rebuilt by the visibility system*/
+    #[serde(skip)]
+    pub light: Vec<RGB>, /*How much light, and what colour, falls on each tile. Black is unlit. Rebuilt by the visibility system*/
+    pub theme: Theme, /*What the walls and floors look like. Picked from the depth when the level is made*/
+    #[serde(skip)]
+    pub avoid: HashSet<usize>, /*Tiles a route should go round if there is any other way, like traps the player knows about. Only filled in while the player's own route is worked out*/
+    #[serde(skip)]
+    pub lit_by: Option<Vec<(Point, i32, RGB)>>, /*Where each light was, how far it reached and its colour when light was last worked out. None means it has to be done again*/
+    #[serde(skip)]
+    pub indexed: Option<BitSet>, /*The entities tile_content was last built from. None means it has to be built again*/
+}
+
+pub fn lights_up(light: &RGB) -> bool { /*Any light at all, however dim or whatever colour, is enough to see a tile by*/
+    light.r + light.g + light.b > 0.0
+}
+
+/*Saving helpers for the map. Each one turns a field into something smaller on the way out, and back again on the way in*/
+mod rle_tiles {
+    use super::TileType;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    pub fn serialize<S: Serializer>(tiles: &[TileType], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut runs : Vec<(TileType, u32)> = Vec::new();
+        for tile in tiles.iter() {
+            match runs.last_mut() {
+                Some((last, count)) if last == tile => *count += 1,
+                _ => runs.push((*tile, 1)),
+            }
+        }
+        runs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<TileType>, D::Error> {
+        let runs : Vec<(TileType, u32)> = Vec::deserialize(deserializer)?;
+        let mut tiles = Vec::new();
+        for (tile, count) in runs {
+            tiles.extend(std::iter::repeat_n(tile, count as usize));
+        }
+        Ok(tiles)
+    }
+}
+
+mod packed_bools {
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    pub fn serialize<S: Serializer>(bools: &[bool], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = vec![0u8; bools.len().div_ceil(8)];
+        for (i, b) in bools.iter().enumerate() {
+            if *b { bytes[i / 8] |= 1 << (i % 8); }
+        }
+        (bools.len(), bytes).serialize(serializer) /*The length is needed too, the last byte might not be full*/
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<bool>, D::Error> {
+        let (len, bytes) : (usize, Vec<u8>) = Deserialize::deserialize(deserializer)?;
+        if bytes.len() < len.div_ceil(8) {
+            return Err(serde::de::Error::custom("not enough bytes for the revealed tiles"));
+        }
+        Ok((0..len).map(|i| bytes[i / 8] & (1 << (i % 8)) != 0).collect())
+    }
+}
+
+impl Map {
+    /*Turns an x,y coordinate into an index in the tile vector. The map is stored row by row, width tiles wide.
+    This and idx_xy are the only places that should do that sum, so debug builds check they are given something on the map*/
+    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
+        debug_assert!(self.in_bounds(x, y), "{},{} is off the {}x{} map", x, y, self.width, self.height);
+        (y as usize * self.width as usize) + x as usize
+    }
+
+    pub fn idx_xy(&self, idx: usize) -> (i32, i32) { /*The other way round, from an index back to x,y*/
+        debug_assert!(idx < (self.width * self.height) as usize, "index {} is off the {}x{} map", idx, self.width, self.height);
+        (idx as i32 % self.width, idx as i32 / self.width)
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool { /*Is this coordinate on the map at all?*/
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    pub fn clamp_point(&self, p: Point) -> Point { /*Pulls a point back onto the map if it has wandered off the edge*/
+        Point::new(p.x.clamp(0, self.width - 1), p.y.clamp(0, self.height - 1))
+    }
+
+    fn is_exit_valid(&self, x: i32, y: i32) -> bool { /*Can something walk onto this tile?*/
+        if !(1..self.width).contains(&x) || !(1..self.height).contains(&y) { return false; } /*Nothing can leave the map*/
+        !self.blocked[self.xy_idx(x, y)]
+    }
+
+    fn is_diagonal_valid(&self, x: i32, y: i32, dx: i32, dy: i32) -> bool { /*Diagonal steps follow the same rule as the player, no slipping between two walls*/
+        self.is_exit_valid(x + dx, y + dy)
+            && self.tiles[self.xy_idx(x + dx, y)] != TileType::Wall
+            && self.tiles[self.xy_idx(x, y + dy)] != TileType::Wall
+    }
+
+    /*What stepping onto a tile costs pathfinding, so routes go round slow ground when that isn't much further. Tiles nothing can walk on never come up*/
+    pub fn tile_cost(&self, idx: usize) -> f32 {
+        let avoid = if self.avoid.contains(&idx) { AVOID_COST } else { 0.0 };
+        avoid + match self.tiles[idx] {
+            TileType::ShallowWater => 2.0, /*Wading takes a second turn*/
+            _ => 1.0,
+        }
+    }
+
+    pub fn is_lit(&self, idx: usize) -> bool {
+        self.light.get(idx).is_some_and(lights_up) /*Missing light means a map that hasn't been through the visibility system yet*/
+    }
+
+    pub fn populate_blocked(&mut self) { /*Mark every wall (and deep water and lava) as blocked, and everything else as open*/
+        for (i, tile) in self.tiles.iter().enumerate() {
+            self.blocked[i] = tile.blocks_movement();
+        }
+    }
+
+    /*Floor tiles on the edge of a room that are a proper gap in the wall, with wall either side of them. That's where the corridors come in*/
+    pub fn door_spots(&self, rooms: &[Rect]) -> Vec<(i32, i32)> {
+        let is_wall = |x: i32, y: i32| !self.in_bounds(x, y) || self.tiles[self.xy_idx(x, y)] == TileType::Wall;
+        let is_doorway = |x: i32, y: i32| self.in_bounds(x, y) && self.tiles[self.xy_idx(x, y)] == TileType::Floor
+            && ((is_wall(x - 1, y) && is_wall(x + 1, y) && !is_wall(x, y - 1) && !is_wall(x, y + 1))
+                || (is_wall(x, y - 1) && is_wall(x, y + 1) && !is_wall(x - 1, y) && !is_wall(x + 1, y)));
+
+        let mut spots = Vec::new();
+        for room in rooms.iter() { /*The wall around a room is one tile outside the floor that was carved*/
+            let mut edge = Vec::new();
+            for x in room.x1..=room.x2 + 1 { edge.push((x, room.y1)); edge.push((x, room.y2 + 1)); }
+            for y in room.y1..=room.y2 + 1 { edge.push((room.x1, y)); edge.push((room.x2 + 1, y)); }
+            for (x, y) in edge {
+                if is_doorway(x, y) && !spots.contains(&(x, y)) { spots.push((x, y)); }
+            }
+        }
+        spots
+    }
+
+    pub fn clear_content_index(&mut self) { /*Forget what was standing where*/
+        for content in self.tile_content.iter_mut() {
+            content.clear();
+        }
+    }
+
+    pub fn move_entity(&mut self, entity: Entity, from: usize, to: usize, blocks: bool) { /*Keeps the index right when something is moved in the middle of a turn, without waiting for the indexing system*/
+        self.tile_content[from].retain(|other| *other != entity);
+        self.tile_content[to].push(entity);
+        if blocks {
+            self.blocked[from] = false;
+            self.blocked[to] = true;
+        }
+    }
+}
+
+impl Map {
+    fn apply_room_to_map(&mut self, room: &Rect) { /*Carve the inside of the room out of the rock, leaving its edge as wall*/
+        for y in room.y1 + 1..=room.y2 {
+            for x in room.x1 + 1..=room.x2 {
+                let idx = self.xy_idx(x, y);
+                self.tiles[idx] = TileType::Floor;
+            }
+        }
+    }
+
+    fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
+        for x in min(x1, x2)..=max(x1, x2) {
+            let idx = self.xy_idx(x, y);
+            if idx > 0 && idx < self.tiles.len() {
+                self.tiles[idx] = TileType::Floor;
+            }
+        }
+    }
+
+    /*Floods a patch of some rooms. Water pools have a deep middle and a shallow edge, lava is lava all the way through.
+    A pool never covers the middle of a room, where the stairs and corridors are, and one that would cut a room off is taken out again*/
+    fn add_pools(&mut self, rng: &mut RandomNumberGenerator, rooms: &[Rect]) {
+        for room in rooms.iter().skip(1) { /*The player always starts on dry land*/
+            if rng.roll_dice(1, POOL_CHANCE) != 1 { continue; }
+            let lava = self.depth >= LAVA_DEPTH && rng.roll_dice(1, 3) == 1;
+            let (w, h) = (rng.range(3, 6), rng.range(3, 5));
+            let x1 = rng.range(room.x1 + 1, room.x2 - w + 2);
+            let y1 = rng.range(room.y1 + 1, room.y2 - h + 2);
+            let (center_x, center_y) = room.center();
+            if (x1..x1 + w).contains(&center_x) && (y1..y1 + h).contains(&center_y) { continue; }
+
+            let before = self.tiles.clone();
+            for y in y1..y1 + h {
+                for x in x1..x1 + w {
+                    let edge = x == x1 || x == x1 + w - 1 || y == y1 || y == y1 + h - 1;
+                    let idx = self.xy_idx(x, y);
+                    self.tiles[idx] = if lava { TileType::Lava } else if edge { TileType::ShallowWater } else { TileType::DeepWater };
+                }
+            }
+            if !self.rooms_connected(rooms) { self.tiles = before; }
+        }
+    }
+
+    fn rooms_connected(&self, rooms: &[Rect]) -> bool { /*Can every room's middle still be walked to from the first room's?*/
+        let Some(first) = rooms.first() else { return true };
+        let mut reached = vec![false; self.tiles.len()];
+        let (start_x, start_y) = first.center();
+        let mut open = vec![self.xy_idx(start_x, start_y)];
+        reached[open[0]] = true;
+        while let Some(idx) = open.pop() {
+            let (x, y) = self.idx_xy(idx);
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if !self.in_bounds(nx, ny) { continue; }
+                let next = self.xy_idx(nx, ny);
+                if !reached[next] && !self.tiles[next].blocks_movement() {
+                    reached[next] = true;
+                    open.push(next);
+                }
+            }
+        }
+        rooms.iter().all(|room| { let (x, y) = room.center(); reached[self.xy_idx(x, y)] })
+    }
+
+    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
+        for y in min(y1, y2)..=max(y1, y2) {
+            let idx = self.xy_idx(x, y);
+            if idx > 0 && idx < self.tiles.len() {
+                self.tiles[idx] = TileType::Floor;
+            }
+        }
+    }
+}
+
+/*Makes a width by height map of rooms joined by corridors. The rooms are returned too, so things can be spawned inside them*/
+pub fn new_map(rng: &mut RandomNumberGenerator, depth: i32, width: i32, height: i32) -> (Map, Vec<Rect>) {
+    let tile_count = (width * height) as usize;
+    let mut map = Map {
+        tiles: vec![TileType::Wall; tile_count], /*Start with solid rock and carve the rooms out of it*/
+        width,
+        height,
+        revealed_tiles: vec![false; tile_count],
+        visible_tiles: vec![false; tile_count],
+        blocked: vec![false; tile_count],
+        tile_content: vec![Vec::new(); tile_count],
+        depth,
+        bloodstains: HashSet::new(),
+        view_blocked: HashSet::new(),
+        light: vec![RGB::from_f32(0.0, 0.0, 0.0); tile_count],
+        theme: theme_for_depth(depth),
+        avoid: HashSet::new(),
+        lit_by: None,
+        indexed: None,
+    };
+
+    let mut rooms : Vec<Rect> = Vec::new();
+    const MIN_SIZE : i32 = 6;
+    const MAX_SIZE : i32 = 10;
+    const MAX_ATTEMPTS : i32 = 1000; /*Give up eventually if the rooms just won't fit*/
+
+    let area_scale = usize::max(1, tile_count / (80 * 43)); /*Somewhere between 10 and 15 rooms for a screen sized map, more for bigger ones*/
+    let room_count = rng.range(10, 16) * area_scale;
+
+    let mut attempts = 0;
+    while rooms.len() < room_count && attempts < MAX_ATTEMPTS {
+        attempts += 1;
+        let w = rng.range(MIN_SIZE, MAX_SIZE);
+        let h = rng.range(MIN_SIZE, MAX_SIZE);
+        let x = rng.roll_dice(1, width - w - 1) - 1;
+        let y = rng.roll_dice(1, height - h - 1) - 1;
+        let new_room = Rect::new(x, y, w, h);
+
+        if rooms.iter().any(|other_room| new_room.intersect(other_room)) { continue; } /*Overlapping rooms are thrown away before anything is carved*/
+
+        map.apply_room_to_map(&new_room);
+
+        if let Some(prev) = rooms.last() { /*Join every room to the one before it, so the whole map is connected*/
+            let (new_x, new_y) = new_room.center();
+            let (prev_x, prev_y) = prev.center();
+            if rng.range(0, 2) == 1 {
+                map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
+                map.apply_vertical_tunnel(prev_y, new_y, new_x);
+            } else {
+                map.apply_vertical_tunnel(prev_y, new_y, prev_x);
+                map.apply_horizontal_tunnel(prev_x, new_x, new_y);
+            }
+        }
+
+        rooms.push(new_room);
+    }
+
+    if let Some(last_room) = rooms.last() { /*The way down is in the middle of the last room, as far along the chain of rooms as you can get*/
+        let (stairs_x, stairs_y) = last_room.center();
+        let stairs_idx = map.xy_idx(stairs_x, stairs_y);
+        map.tiles[stairs_idx] = TileType::DownStairs;
+    }
+
+    if depth > 1 { /*The way back up is where the player arrives, in the middle of the first room*/
+        if let Some(first_room) = rooms.first() {
+            let (stairs_x, stairs_y) = first_room.center();
+            let stairs_idx = map.xy_idx(stairs_x, stairs_y);
+            map.tiles[stairs_idx] = TileType::UpStairs;
+        }
+    }
+
+    map.add_pools(rng, &rooms);
+    map.populate_blocked();
+    (map, rooms)
+}
+
+/*These two traits from rltk let the library's field of view (and later pathfinding) code understand our map*/
+impl Algorithm2D for Map {
+    fn dimensions(&self) -> Point {
+        Point::new(self.width, self.height)
+    }
+
+    fn point2d_to_index(&self, pt: Point) -> usize { /*Not xy_idx, field of view asks about points just past the edge before it checks in_bounds*/
+        (pt.y * self.width + pt.x) as usize
+    }
+}
+
+impl BaseMap for Map {
+    fn is_opaque(&self, idx: usize) -> bool { /*Walls block line of sight, and so does anything standing in the way that blocks visibility*/
+        self.tiles[idx] == TileType::Wall || self.view_blocked.contains(&idx)
+    }
+
+    fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> { /*The tiles you can step to from here, and what it costs. Used by pathfinding*/
+        let mut exits = SmallVec::new();
+        let (x, y) = self.idx_xy(idx);
+        let w = self.width as usize;
+        let mut add = |to: usize, step: f32| exits.push((to, step * self.tile_cost(to))); /*The length of the step, times how slow the ground it lands on is*/
+
+        if self.is_exit_valid(x - 1, y) { add(idx - 1, 1.0) };
+        if self.is_exit_valid(x + 1, y) { add(idx + 1, 1.0) };
+        if self.is_exit_valid(x, y - 1) { add(idx - w, 1.0) };
+        if self.is_exit_valid(x, y + 1) { add(idx + w, 1.0) };
+
+        /*Diagonals cost a bit more, so paths don't zig-zag for no reason*/
+        if self.is_diagonal_valid(x, y, -1, -1) { add((idx - w) - 1, 1.45) };
+        if self.is_diagonal_valid(x, y, 1, -1) { add((idx - w) + 1, 1.45) };
+        if self.is_diagonal_valid(x, y, -1, 1) { add((idx + w) - 1, 1.45) };
+        if self.is_diagonal_valid(x, y, 1, 1) { add((idx + w) + 1, 1.45) };
+
+        exits
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 { /*Straight line distance, used by A* to guess which way to go*/
+        let (x1, y1) = self.idx_xy(idx1);
+        let (x2, y2) = self.idx_xy(idx2);
+        let p1 = Point::new(x1, y1);
+        let p2 = Point::new(x2, y2);
+        rltk::DistanceAlg::Pythagoras.distance2d(p1, p2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_map(width: i32, height: i32) -> Map { /*Floor everywhere, edges included, so the edge of the map is the only thing in the way*/
+        let tile_count = (width * height) as usize;
+        Map {
+            tiles: vec![TileType::Floor; tile_count],
+            width,
+            height,
+            revealed_tiles: vec![false; tile_count],
+            visible_tiles: vec![false; tile_count],
+            blocked: vec![false; tile_count],
+            tile_content: vec![Vec::new(); tile_count],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tiles_and_revealed_tiles_survive_saving() {
+        let mut map = open_map(7, 3); /*21 tiles, so the last byte of revealed tiles is only part full*/
+        let pattern = [TileType::Wall, TileType::Wall, TileType::Floor, TileType::ShallowWater, TileType::DeepWater, TileType::Lava, TileType::DownStairs, TileType::UpStairs];
+        for (i, tile) in map.tiles.iter_mut().enumerate() { *tile = pattern[(i * 5 / 3) % pattern.len()]; }
+        for (i, revealed) in map.revealed_tiles.iter_mut().enumerate() { *revealed = i % 3 == 0 || i == 20; }
+
+        let saved = serde_json::to_string(&map).expect("Unable to save map");
+        let loaded : Map = serde_json::from_str(&saved).expect("Unable to load map");
+        assert!(loaded.tiles == map.tiles);
+        assert_eq!(loaded.revealed_tiles, map.revealed_tiles);
+    }
+
+    #[test]
+    fn packed_bools_round_trip_at_every_length() {
+        for len in [0, 1, 7, 8, 9, 16, 17] {
+            let bools : Vec<bool> = (0..len).map(|i| i % 2 == 0 || i == len - 1).collect();
+            let mut saved = Vec::new();
+            packed_bools::serialize(&bools, &mut serde_json::Serializer::new(&mut saved)).expect("Unable to pack");
+            let loaded = packed_bools::deserialize(&mut serde_json::Deserializer::from_slice(&saved)).expect("Unable to unpack");
+            assert_eq!(loaded, bools, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn too_few_packed_bytes_is_an_error() {
+        let loaded = packed_bools::deserialize(&mut serde_json::Deserializer::from_str("[9,[255]]"));
+        assert!(loaded.is_err());
+    }
+
+    #[test]
+    fn exits_stay_on_the_map_and_off_walls() {
+        let mut map = open_map(6, 5);
+        for x in 0..map.width { /*A wall along the top row, with floor on the other three edges*/
+            let idx = map.xy_idx(x, 0);
+            map.tiles[idx] = TileType::Wall;
+        }
+        let wall = map.xy_idx(3, 2);
+        map.tiles[wall] = TileType::Wall;
+        map.populate_blocked();
+
+        let (w, h) = (map.width, map.height);
+        let edges = (0..w).flat_map(|x| [(x, 0), (x, h - 1)]).chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]));
+        for (x, y) in edges.chain([(2, 2), (4, 2), (3, 1), (3, 3)]) { /*The edges and corners, and all round the lone wall*/
+            let idx = map.xy_idx(x, y);
+            for (exit, _cost) in map.get_available_exits(idx) {
+                assert!(exit < map.tiles.len(), "{},{} leads off the map", x, y);
+                let (ex, ey) = map.idx_xy(exit);
+                assert!((ex - x).abs() <= 1 && (ey - y).abs() <= 1, "{},{} wrapped round to {},{}", x, y, ex, ey);
+                assert!(map.tiles[exit] != TileType::Wall, "{},{} leads into the wall at {},{}", x, y, ex, ey);
+            }
+        }
+    }
+
+    #[test]
+    fn every_tile_index_converts_back_to_its_coordinate() {
+        let map = open_map(7, 4); /*Not square, so mixing up width and height shows*/
+        let mut seen = vec![false; map.tiles.len()];
+        for y in 0..map.height {
+            for x in 0..map.width {
+                let idx = map.xy_idx(x, y);
+                assert!(!seen[idx], "{},{} shares index {}", x, y, idx);
+                seen[idx] = true;
+                assert_eq!(map.idx_xy(idx), (x, y));
+            }
+        }
+        assert!(seen.iter().all(|s| *s)); /*And every index is some coordinate*/
+    }
+
+    #[test]
+    fn paths_go_round_shallow_water_when_it_is_quicker() {
+        let mut map = open_map(7, 5);
+        for x in 2..=4 { /*Water straight across the direct route, with dry floor in the row above and below it*/
+            let idx = map.xy_idx(x, 2);
+            map.tiles[idx] = TileType::ShallowWater;
+        }
+        map.populate_blocked();
+
+        let (start, end) = (map.xy_idx(1, 2), map.xy_idx(5, 2));
+        let path = rltk::a_star_search(start, end, &map);
+        assert!(path.success);
+        assert_eq!(path.steps.last(), Some(&end));
+        assert!(path.steps.iter().all(|idx| map.tiles[*idx] != TileType::ShallowWater), "the path waded instead of going round");
+    }
+}
@@ -0,0 +1,130 @@
+use specs::prelude::*;
+use specs::world::Index;
+use super::{Map, Position, BlocksTile, Size, FloatingText, ParticleLifetime, movement::{MovedChannel, MovedReaders}};
+
+pub struct MapIndexingSystem {}
+
+impl<'a> System<'a> for MapIndexingSystem { /*Rebuilds the map's record of what is standing where, so nothing has to scan every entity to find out. Skipped on turns where nothing moved, appeared or went away*/
+    type SystemData = ( WriteExpect<'a, Map>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, BlocksTile>,
+                        ReadStorage<'a, Size>,
+                        ReadStorage<'a, FloatingText>,
+                        ReadStorage<'a, ParticleLifetime>,
+                        Entities<'a>,
+                        ReadExpect<'a, MovedChannel>,
+                        WriteExpect<'a, MovedReaders>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (mut map, position, blockers, sizes, floating_text, particles, entities, moved, mut readers) = data;
+
+        let anything_moved = moved.read(&mut readers.indexing).count() > 0; /*Reads them all, so they aren't seen again next turn*/
+        let placed_or_removed : Vec<Index> = position.channel().read(&mut readers.placed).chain(blockers.channel().read(&mut readers.blocking))
+            .filter_map(|event| match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Removed(id) => Some(*id),
+                ComponentEvent::Modified(_) => None, /*Sent whenever a position is borrowed to change, moved or not. Real moves come through the moved channel*/
+            })
+            .collect();
+        if let Some(indexed) = &map.indexed {
+            let matters = |id : Index| {
+                let entity = entities.entity(id);
+                indexed.contains(id) /*Something that was indexed went away, stopped blocking or started*/
+                    || (entities.is_alive(entity) && position.get(entity).is_some() && floating_text.get(entity).is_none() && particles.get(entity).is_none()) /*Something new turned up*/
+            };
+            if !anything_moved && !placed_or_removed.into_iter().any(matters) { return; } /*Nothing has changed since the last rebuild*/
+        }
+
+        map.populate_blocked(); /*Start from just the walls*/
+        map.clear_content_index();
+        let mut indexed = BitSet::new();
+        for (entity, position, _not_text, _not_particle) in (&entities, &position, !&floating_text, !&particles).join() { /*Damage numbers and particles are just drawn there, nothing is standing on the tile*/
+            indexed.add(entity.id());
+            let size = sizes.get(entity).unwrap_or(&Size::SINGLE);
+            for (x, y) in size.tiles(position.x, position.y) { /*Something big is found on every tile it covers, so bumping any of them reaches it*/
+                if !map.in_bounds(x, y) { continue; }
+                let idx = map.xy_idx(x, y);
+
+                if blockers.get(entity).is_some() { /*Entities that block their tile mark it as blocked*/
+                    map.blocked[idx] = true;
+                }
+
+                map.tile_content[idx].push(entity); /*Everything else with a position is indexed, blocking or not*/
+            }
+        }
+        map.indexed = Some(indexed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rltk::RGB;
+    use crate::{map::TileType, movement::{self, MovedEvent}, saveload_system::register_components};
+
+    fn open_world() -> World {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        movement::register(&mut ecs);
+        let (width, height) = (10, 10);
+        let tile_count = (width * height) as usize;
+        ecs.insert(Map { tiles: vec![TileType::Floor; tile_count], blocked: vec![false; tile_count], tile_content: vec![Vec::new(); tile_count], width, height, ..Default::default() });
+        ecs
+    }
+
+    fn damage_number(ecs: &mut World, x: i32, y: i32) -> Entity {
+        ecs.create_entity()
+            .with(Position { x, y })
+            .with(FloatingText { text: "3".to_string(), remaining_ms: 600.0, dy_per_ms: 0.001, offset_y: 0.0, lifetime_ms: 600.0, fg: RGB::named(rltk::RED) })
+            .build()
+    }
+
+    fn rebuilt(ecs: &mut World) -> bool { /*Runs a turn's indexing. A rebuild starts over from the walls, which clears the mark left on the corner*/
+        ecs.write_resource::<Map>().blocked[0] = true;
+        MapIndexingSystem{}.run_now(ecs);
+        ecs.maintain();
+        !ecs.fetch::<Map>().blocked[0]
+    }
+
+    #[test]
+    fn idle_turns_do_not_rebuild_the_index() {
+        let mut ecs = open_world();
+        let monster = ecs.create_entity().with(Position { x: 2, y: 2 }).with(BlocksTile {}).build();
+        let bystander = ecs.create_entity().with(Position { x: 4, y: 4 }).build();
+        damage_number(&mut ecs, 6, 6);
+        let particle = ecs.create_entity().with(Position { x: 7, y: 7 }).with(ParticleLifetime { lifetime_ms: 200.0 }).build();
+        assert!(rebuilt(&mut ecs), "a new map has to be indexed");
+        {
+            let map = ecs.fetch::<Map>();
+            assert!(map.blocked[map.xy_idx(2, 2)]);
+            assert_eq!(map.tile_content[map.xy_idx(4, 4)], vec![bystander]);
+            assert!(map.tile_content[map.xy_idx(6, 6)].is_empty(), "damage numbers aren't standing anywhere");
+            assert!(map.tile_content[map.xy_idx(7, 7)].is_empty(), "nor are particles");
+        }
+
+        let mut rebuilds = 0;
+        for _turn in 0..10 {
+            for _position in (&mut ecs.write_storage::<Position>()).join() {} /*Borrowing positions to change, like the AI does, isn't a move*/
+            if rebuilt(&mut ecs) { rebuilds += 1; }
+        }
+        damage_number(&mut ecs, 2, 3);
+        ecs.delete_entity(particle).expect("Unable to delete entity");
+        if rebuilt(&mut ecs) { rebuilds += 1; }
+        assert_eq!(rebuilds, 0, "nothing that stands on a tile changed");
+
+        ecs.write_storage::<BlocksTile>().remove(monster); /*Like a door opening*/
+        assert!(rebuilt(&mut ecs));
+        assert!(!ecs.fetch::<Map>().blocked[ecs.fetch::<Map>().xy_idx(2, 2)]);
+        ecs.write_storage::<Position>().remove(bystander); /*Like an item being picked up*/
+        assert!(rebuilt(&mut ecs));
+        ecs.delete_entity(monster).expect("Unable to delete entity");
+        assert!(rebuilt(&mut ecs));
+        let newcomer = ecs.create_entity().with(Position { x: 5, y: 5 }).build();
+        assert!(rebuilt(&mut ecs));
+        ecs.write_storage::<Position>().insert(newcomer, Position { x: 5, y: 6 }).expect("Unable to insert");
+        ecs.write_resource::<movement::MovedChannel>().single_write(MovedEvent { entity: newcomer, x: 5, y: 6 });
+        assert!(rebuilt(&mut ecs));
+        assert!(!rebuilt(&mut ecs), "and idle again after");
+        let map = ecs.fetch::<Map>();
+        assert_eq!(map.tile_content[map.xy_idx(5, 6)], vec![newcomer]);
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+use rltk::{RGB, FontCharType};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use super::{Map, TileType};
+
+const CAVERN_DEPTH : i32 = 4; /*The first level drawn as caverns instead of dungeon*/
+
+/*How a level's tiles look. Only changes the drawing, the tiles themselves are the same at every depth.
+Water and lava ripple the same way everywhere, so draw_map animates those itself and never asks the theme*/
+pub trait MapTheme : Send + Sync {
+    fn name(&self) -> &'static str; /*What a save calls it*/
+    fn tile_glyph(&self, idx: usize, map: &Map) -> (FontCharType, RGB, RGB); /*Glyph, foreground and background*/
+}
+
+pub struct DungeonTheme;
+
+impl MapTheme for DungeonTheme {
+    fn name(&self) -> &'static str { "Dungeon" }
+
+    fn tile_glyph(&self, idx: usize, map: &Map) -> (FontCharType, RGB, RGB) {
+        match map.tiles[idx] {
+            TileType::Floor => (rltk::to_cp437('.'), RGB::from_f32(0.0, 0.5, 0.5), RGB::from_f32(0., 0., 0.)),
+            TileType::Wall => {
+                let (x, y) = map.idx_xy(idx);
+                (wall_glyph(map, x, y), RGB::from_f32(0.0, 1.0, 0.0), RGB::from_f32(0., 0., 0.))
+            }
+            tile => shared_glyph(tile),
+        }
+    }
+}
+
+pub struct CavernTheme; /*Rough brown rock, deeper down*/
+
+impl MapTheme for CavernTheme {
+    fn name(&self) -> &'static str { "Cavern" }
+
+    fn tile_glyph(&self, idx: usize, map: &Map) -> (FontCharType, RGB, RGB) {
+        match map.tiles[idx] {
+            TileType::Floor => (rltk::to_cp437(','), RGB::from_f32(0.45, 0.35, 0.25), RGB::from_f32(0., 0., 0.)),
+            TileType::Wall => (rltk::to_cp437('▓'), RGB::from_f32(0.6, 0.45, 0.3), RGB::from_f32(0.1, 0.07, 0.05)),
+            tile => shared_glyph(tile),
+        }
+    }
+}
+
+/*A box drawing line joining the wall up with whichever of its four neighbours are walls too. Only neighbours the player has seen count,
+so the shape of rock nobody has looked at yet isn't given away*/
+pub fn wall_glyph(map: &Map, x: i32, y: i32) -> FontCharType {
+    let is_wall = |x: i32, y: i32| {
+        map.in_bounds(x, y) && { let idx = map.xy_idx(x, y); map.tiles[idx] == TileType::Wall && map.revealed_tiles[idx] }
+    };
+    let mut mask = 0;
+    if is_wall(x, y - 1) { mask += 1; }
+    if is_wall(x, y + 1) { mask += 2; }
+    if is_wall(x - 1, y) { mask += 4; }
+    if is_wall(x + 1, y) { mask += 8; }
+    rltk::to_cp437(match mask {
+        0 => '○', /*A pillar on its own*/
+        1..=3 => '║', /*Up, down, or both*/
+        4 | 8 | 12 => '═', /*Left, right, or both*/
+        5 => '╝',
+        6 => '╗',
+        7 => '╣',
+        9 => '╚',
+        10 => '╔',
+        11 => '╠',
+        13 => '╩',
+        14 => '╦',
+        _ => '╬',
+    })
+}
+
+fn shared_glyph(tile: TileType) -> (FontCharType, RGB, RGB) { /*Stairs look the same in every theme, so the way on is never in doubt*/
+    match tile {
+        TileType::UpStairs => (rltk::to_cp437('<'), RGB::from_f32(0.0, 1.0, 1.0), RGB::from_f32(0., 0., 0.)),
+        _ => (rltk::to_cp437('>'), RGB::from_f32(0.0, 1.0, 1.0), RGB::from_f32(0., 0., 0.)),
+    }
+}
+
+pub fn theme_for_depth(depth: i32) -> Theme {
+    if depth >= CAVERN_DEPTH { Theme(Arc::new(CavernTheme)) } else { Theme(Arc::new(DungeonTheme)) }
+}
+
+/*The theme a map is drawn with. Saved by name, and a save from before themes gets the dungeon*/
+#[derive(Clone)]
+pub struct Theme(Arc<dyn MapTheme>);
+
+impl Default for Theme {
+    fn default() -> Theme { Theme(Arc::new(DungeonTheme)) }
+}
+
+impl std::ops::Deref for Theme {
+    type Target = dyn MapTheme;
+    fn deref(&self) -> &Self::Target { self.0.as_ref() }
+}
+
+impl Serialize for Theme {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.name().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Theme {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Theme, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match name.as_str() {
+            "Cavern" => Ok(Theme(Arc::new(CavernTheme))),
+            "Dungeon" => Ok(Theme(Arc::new(DungeonTheme))),
+            _ => Err(serde::de::Error::custom(format!("unknown map theme {}", name))),
+        }
+    }
+}
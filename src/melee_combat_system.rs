@@ -0,0 +1,125 @@
+use specs::prelude::*;
+use rltk::RGB;
+use super::{CombatStats, WantsToMelee, SufferDamage, Name, Position, Equipped, MeleePowerBonus, DefenseBonus, DamageType, gamelog::{GameLog, Logger}, particle_system::ParticleBuilder,
+    Knockback, Map, TileType, BlocksTile, Player, Poisonous, DamageOverTime, Size,
+    movement::{MovedChannel, MovedEvent}};
+
+const SLAM_DAMAGE : i32 = 2; /*For every tile of push left when something is knocked into a wall*/
+
+pub struct MeleeCombatSystem {}
+
+impl<'a> System<'a> for MeleeCombatSystem { /*Turns melee intents into queued damage*/
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, WantsToMelee>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        WriteStorage<'a, Position>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        ReadStorage<'a, DefenseBonus>,
+                        ReadStorage<'a, Knockback>,
+                        WriteExpect<'a, Map>,
+                        ReadStorage<'a, BlocksTile>,
+                        WriteExpect<'a, MovedChannel>,
+                        ReadStorage<'a, Player>,
+                        WriteExpect<'a, rltk::Point>,
+                        ReadStorage<'a, Poisonous>,
+                        WriteStorage<'a, DamageOverTime>,
+                        ReadStorage<'a, Size>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut wants_melee, names, combat_stats, mut inflict_damage, mut log, mut particle_builder, mut positions, equipped, melee_power_bonuses, defense_bonuses,
+            knockbacks, mut map, blockers, mut moved, players, mut player_pos, poisonous, mut poisoned, sizes) = data;
+
+        let mut pushes = Vec::new(); /*Knockbacks are done once every attack has been worked out, since they need to move things*/
+        let mut poisonings = Vec::new();
+
+        for (entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
+            if stats.hp > 0 { /*Dead things don't get to attack*/
+                if let Some(target_stats) = combat_stats.get(wants_melee.target) {
+                    if target_stats.hp > 0 {
+                        let target_name = names.get(wants_melee.target).map_or("something", |n| n.name.as_str());
+                        let weapons : Vec<&MeleePowerBonus> = (&melee_power_bonuses, &equipped).join() /*Whatever the attacker is wielding adds to their power*/
+                            .filter(|(_bonus, gear)| gear.owner == entity)
+                            .map(|(bonus, _gear)| bonus)
+                            .collect();
+                        let offensive_bonus : i32 = weapons.iter().map(|bonus| bonus.power).sum();
+                        let damage_type = weapons.iter().map(|bonus| bonus.damage_type).find(|kind| *kind != DamageType::Physical).unwrap_or_default(); /*Bare hands and plain blades hit for physical damage*/
+                        let defensive_bonus : i32 = (&defense_bonuses, &equipped).join() /*and whatever the target is wearing adds to their defense*/
+                            .filter(|(_bonus, gear)| gear.owner == wants_melee.target)
+                            .map(|(bonus, _gear)| bonus.defense)
+                            .sum();
+                        let damage = i32::max(0, (stats.power + offensive_bonus) - (target_stats.defense + defensive_bonus)); /*Defense soaks up some of the hit, but never heals*/
+                        if damage > 0 {
+                            Logger::new().npc_name(&name.name).append("hits").npc_name(target_name).append("for").damage(damage).append("damage").log(&mut log);
+                            SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage, damage_type);
+                            if let Some(pos) = positions.get(wants_melee.target) { /*Flash the tile that got hit*/
+                                particle_builder.request(pos.x, pos.y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), rltk::to_cp437('‼'), 200.0);
+                            }
+                            let knockback = knockbacks.get(entity).map(|knockback| knockback.distance).into_iter() /*A heavy weapon, or a big enough monster*/
+                                .chain((&knockbacks, &equipped).join().filter(|(_knockback, gear)| gear.owner == entity).map(|(knockback, _gear)| knockback.distance))
+                                .max().unwrap_or(0);
+                            if knockback > 0 && sizes.get(wants_melee.target).is_none() { pushes.push((entity, wants_melee.target, knockback)); } /*Big things are too heavy to shift*/
+                            let poison = poisonous.get(entity).into_iter() /*A venomous bite, or a poisoned blade*/
+                                .chain((&poisonous, &equipped).join().filter(|(_poison, gear)| gear.owner == entity).map(|(poison, _gear)| poison))
+                                .next();
+                            if let Some(poison) = poison { poisonings.push((wants_melee.target, poison.damage, poison.turns)); }
+                        } else {
+                            Logger::new().npc_name(&name.name).append("is unable to hurt").npc_name(target_name).log(&mut log);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (target, damage, turns) in poisonings {
+            DamageOverTime::apply(&mut poisoned, target, damage, turns);
+            let target_name = names.get(target).map_or("Something", |n| n.name.as_str());
+            Logger::new().npc_name(target_name).append("is poisoned!").log(&mut log);
+        }
+
+        for (attacker, target, distance) in pushes {
+            let (from, start) = match (positions.get(attacker), positions.get(target)) {
+                (Some(from), Some(start)) => ((from.x, from.y), (start.x, start.y)),
+                _ => continue,
+            };
+            let (dx, dy) = ((start.0 - from.0).signum(), (start.1 - from.1).signum()); /*Straight away from the attacker*/
+            let (mut x, mut y) = start;
+            let mut travelled = 0;
+            let mut slammed = false;
+            while travelled < distance { /*One tile at a time, stopping at the first thing in the way*/
+                let (next_x, next_y) = (x + dx, y + dy);
+                if !map.in_bounds(next_x, next_y) || map.tiles[map.xy_idx(next_x, next_y)] == TileType::Wall { slammed = true; break; }
+                let idx = map.xy_idx(next_x, next_y);
+                if map.tiles[idx] == TileType::DeepWater { break; } /*Stops at the bank. Lava doesn't stop anything, that's the fun of it*/
+                if map.tile_content[idx].iter().any(|other| blockers.get(*other).is_some() || combat_stats.get(*other).is_some()) { break; } /*Someone else is standing there*/
+                x = next_x;
+                y = next_y;
+                travelled += 1;
+            }
+
+            let target_name = names.get(target).map_or("something", |n| n.name.as_str());
+            if travelled > 0 {
+                let (from_idx, to_idx) = (map.xy_idx(start.0, start.1), map.xy_idx(x, y));
+                map.move_entity(target, from_idx, to_idx, blockers.get(target).is_some());
+                if let Some(pos) = positions.get_mut(target) {
+                    pos.x = x;
+                    pos.y = y;
+                }
+                moved.single_write(MovedEvent { entity: target, x, y }); /*Being thrown onto a trap still sets it off*/
+                if players.get(target).is_some() { *player_pos = rltk::Point::new(x, y); }
+                Logger::new().npc_name(target_name).append("is knocked back").log(&mut log);
+            }
+            if slammed {
+                let damage = SLAM_DAMAGE * (distance - travelled);
+                Logger::new().npc_name(target_name).append("slams into the wall for").damage(damage).append("damage").log(&mut log);
+                SufferDamage::new_damage(&mut inflict_damage, target, damage, DamageType::Physical);
+            }
+        }
+
+        wants_melee.clear(); /*Every intent has been handled, so remove them all*/
+    }
+}
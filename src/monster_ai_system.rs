@@ -0,0 +1,214 @@
+use specs::prelude::*;
+use super::{Viewshed, Monster, Bystander, Position, Map, TileType, WantsToMelee, Confusion, CombatStats, Speed, Faction, Player, Charmed, Name, Size, energy_system,
+    gamelog::{GameLog, Logger}, movement::{MovedChannel, MovedEvent},
+    factions::{FactionTable, Reaction, PLAYER_FACTION, MONSTER_FACTION}};
+use rltk::{Point, RandomNumberGenerator, BaseMap};
+
+const FLEE_SEARCH_DEPTH : f32 = 50.0; /*How far out from the player the flee map bothers to look*/
+const FOLLOW_DISTANCE : f32 = 2.5; /*A charmed monster with nothing to fight keeps about this close to the player*/
+
+pub struct MonsterAI {
+    pub round: usize, /*Fast monsters get several rounds in one turn, counting from 0*/
+}
+
+/*Where a fleeing monster should step to: the open tile next to it that is furthest from whatever it is running from.
+None if every way out is no further away than where it stands, so it's cornered*/
+fn flee_step(map: &mut Map, danger: Point, current: usize) -> Option<usize> {
+    map.blocked[current] = false; /*Otherwise the monster's own tile never gets a distance*/
+    let flee_map = rltk::DijkstraMap::new(map.width, map.height, &[map.xy_idx(danger.x, danger.y)], &*map, FLEE_SEARCH_DEPTH);
+    map.blocked[current] = true;
+
+    let here = flee_map.map[current];
+    map.get_available_exits(current).iter()
+        .map(|(idx, _cost)| (*idx, flee_map.map[*idx]))
+        .filter(|(_idx, distance)| *distance < f32::MAX && *distance > here) /*f32::MAX is a tile the search never reached*/
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(idx, _distance)| idx)
+}
+
+/*Whether something of this size can move its corner from one tile to next. Its own tiles don't count as being in the way*/
+fn footprint_clear(map: &Map, size: &Size, from: Point, next: usize) -> bool {
+    let own = size.tiles(from.x, from.y);
+    let (x, y) = map.idx_xy(next);
+    size.tiles(x, y).iter().all(|(tile_x, tile_y)| {
+        if !map.in_bounds(*tile_x, *tile_y) { return false; }
+        let idx = map.xy_idx(*tile_x, *tile_y);
+        own.contains(&(*tile_x, *tile_y)) || (!map.blocked[idx] && !map.tiles[idx].blocks_movement())
+    })
+}
+
+fn set_blocked(map: &mut Map, size: &Size, corner: Point, blocked: bool) {
+    for (x, y) in size.tiles(corner.x, corner.y) {
+        if map.in_bounds(x, y) { let idx = map.xy_idx(x, y); map.blocked[idx] = blocked; }
+    }
+}
+
+/*Something that can fight, where it is standing, how much room it takes up and whose side it is on*/
+struct Combatant {
+    entity: Entity,
+    pos: Point,
+    size: Size,
+    faction: String,
+}
+
+/*Which side something is on right now. Charmed monsters are on the player's side whatever their own faction is, so when the charm wears off they are back where they started*/
+fn faction_of(entity: Entity, factions: &ReadStorage<Faction>, players: &ReadStorage<Player>, charmed: &WriteStorage<Charmed>) -> String {
+    match factions.get(entity) {
+        _ if charmed.get(entity).is_some() => PLAYER_FACTION.to_string(),
+        Some(faction) => faction.name.clone(),
+        None if players.get(entity).is_some() => PLAYER_FACTION.to_string(),
+        None => MONSTER_FACTION.to_string(),
+    }
+}
+
+/*The nearest thing in view that this monster reacts to in the given way, if there is one. Seeing any part of something big counts*/
+fn nearest(me: Entity, my_faction: &str, from: Point, viewshed: &Viewshed, combatants: &[Combatant], table: &FactionTable, reaction: Reaction) -> Option<(Entity, Point, Size)> {
+    combatants.iter()
+        .filter(|other| other.entity != me && other.size.tiles(other.pos.x, other.pos.y).iter().any(|(x, y)| viewshed.visible_tiles.contains(&Point::new(*x, *y))))
+        .filter(|other| table.reaction(my_faction, &other.faction) == reaction)
+        .map(|other| (other.entity, other.pos, other.size, rltk::DistanceAlg::Pythagoras.distance2d(from, other.pos)))
+        .min_by(|a, b| a.3.partial_cmp(&b.3).unwrap())
+        .map(|(entity, pos, size, _distance)| (entity, pos, size))
+}
+
+/*Stepping into shallow water costs a monster its next go, the same as it costs the player their next turn*/
+fn wade(map: &Map, idx: usize, speed: Option<&mut Speed>) {
+    if map.tiles[idx] != TileType::ShallowWater { return; }
+    if let Some(speed) = speed { speed.energy -= speed.threshold; }
+}
+
+impl<'a> System<'a> for MonsterAI { /*Monsters walk towards the nearest enemy they can see, by the faction table, and attack once they are next to it. Bystanders just wander*/
+    type SystemData = ( WriteExpect<'a, Map>,
+                        ReadExpect<'a, Point>,
+                        Entities<'a>,
+                        WriteStorage<'a, Viewshed>,
+                        WriteStorage<'a, Monster>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, WantsToMelee>,
+                        WriteStorage<'a, Confusion>,
+                        ReadStorage<'a, Bystander>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, MovedChannel>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, Speed>,
+                        ReadStorage<'a, Faction>,
+                        ReadStorage<'a, Player>,
+                        ReadExpect<'a, FactionTable>,
+                        WriteStorage<'a, Charmed>,
+                        ReadStorage<'a, Name>,
+                        WriteExpect<'a, GameLog>,
+                        ReadStorage<'a, Size>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (mut map, player_pos, entities, mut viewshed, mut monster, mut position, mut wants_to_melee, mut confused, bystanders, mut rng, mut moved, combat_stats, mut speeds,
+            factions, players, faction_table, mut charmed, names, mut log, sizes) = data;
+
+        let mut combatants : Vec<Combatant> = (&entities, &position, &combat_stats).join() /*Everything worth fighting or fleeing, kept up to date as the monsters move*/
+            .filter(|(_entity, _pos, stats)| stats.hp > 0)
+            .map(|(entity, pos, _stats)| Combatant {
+                entity,
+                pos: Point::new(pos.x, pos.y),
+                size: *sizes.get(entity).unwrap_or(&Size::SINGLE),
+                faction: faction_of(entity, &factions, &players, &charmed),
+            })
+            .collect();
+
+        for (entity, viewshed, monster, pos, ()) in (&entities, &mut viewshed, &mut monster, &mut position, !&bystanders).join() {
+            if combat_stats.get(entity).is_some_and(|stats| stats.hp < 1) { continue; } /*Killed earlier this turn, and only waiting to be deleted*/
+            if !energy_system::try_act(speeds.get_mut(entity), self.round) { continue; } /*Not enough energy for another go yet*/
+
+            if let Some(charm) = charmed.get_mut(entity) { /*The charm wears off a little every go, confused or not*/
+                charm.turns -= 1;
+                if charm.turns < 1 {
+                    charmed.remove(entity);
+                    let name = names.get(entity).map_or("something", |n| n.name.as_str());
+                    Logger::new().append("Watch out! The").npc_name(name.to_lowercase()).append("is no longer charmed!").log(&mut log);
+                    let faction = faction_of(entity, &factions, &players, &charmed);
+                    if let Some(me) = combatants.iter_mut().find(|c| c.entity == entity) { me.faction = faction; }
+                }
+            }
+
+            if let Some(i_am_confused) = confused.get_mut(entity) { /*Confused monsters lose their turn*/
+                i_am_confused.turns -= 1;
+                if i_am_confused.turns < 1 {
+                    confused.remove(entity);
+                }
+                continue;
+            }
+
+            if let Some(stats) = combat_stats.get(entity) { /*Under a quarter of its health left*/
+                monster.fleeing = stats.hp * 4 < stats.max_hp;
+            }
+
+            let here = Point::new(pos.x, pos.y);
+            let size = *sizes.get(entity).unwrap_or(&Size::SINGLE);
+            let my_faction = combatants.iter().find(|c| c.entity == entity).map_or(MONSTER_FACTION.to_string(), |c| c.faction.clone());
+            let target = nearest(entity, &my_faction, here, viewshed, &combatants, &faction_table, Reaction::Attack);
+            let threat = nearest(entity, &my_faction, here, viewshed, &combatants, &faction_table, Reaction::Flee);
+            let danger = match (threat, monster.fleeing) { /*Run from anything it is scared of, or from whatever it was fighting once badly hurt*/
+                (Some((_threat, threat_pos, _size)), _) => Some(threat_pos),
+                (None, true) => target.map(|(_target, target_pos, _size)| target_pos),
+                (None, false) => None,
+            };
+
+            let mut next_step = None;
+            if let Some(danger) = danger {
+                let current = map.xy_idx(pos.x, pos.y);
+                next_step = flee_step(&mut map, danger, current); /*If there is nowhere left to run, it fights like normal*/
+            }
+
+            if next_step.is_none() {
+                let target_pos = match target {
+                    Some((target, target_pos, target_size)) => {
+                        if size.touching(here, &target_size, target_pos) { /*Right next to it, so attack instead of moving*/
+                            wants_to_melee.insert(entity, WantsToMelee { target }).expect("Unable to insert attack");
+                            continue;
+                        }
+                        target_pos
+                    }
+                    None if charmed.get(entity).is_some() => { /*Nothing to fight, so a charmed monster tags along behind the player*/
+                        if rltk::DistanceAlg::Pythagoras.distance2d(here, *player_pos) <= FOLLOW_DISTANCE { continue; }
+                        *player_pos
+                    }
+                    None => continue, /*Nothing it wants to fight in sight*/
+                };
+
+                let target_idx = map.xy_idx(target_pos.x, target_pos.y);
+                let target_blocked = map.blocked[target_idx];
+                map.blocked[target_idx] = false; /*Other monsters block their tile, which would stop the path ever reaching them*/
+                set_blocked(&mut map, &size, here, false); /*and something big would find its way blocked by itself*/
+                let path = rltk::a_star_search(map.xy_idx(pos.x, pos.y), target_idx, &*map);
+                set_blocked(&mut map, &size, here, true);
+                map.blocked[target_idx] = target_blocked;
+                if path.success && path.steps.len() > 1 { /*steps[1] is the next tile along*/
+                    next_step = Some(path.steps[1]);
+                }
+            }
+
+            if let Some(next) = next_step.filter(|next| footprint_clear(&map, &size, here, *next)) { /*Someone else may have moved there already this turn, or something big may not fit*/
+                set_blocked(&mut map, &size, here, false); /*Keep the blocked list up to date so monsters don't stack*/
+                (pos.x, pos.y) = map.idx_xy(next);
+                set_blocked(&mut map, &size, Point::new(pos.x, pos.y), true);
+                moved.single_write(MovedEvent { entity, x: pos.x, y: pos.y });
+                wade(&map, next, speeds.get_mut(entity));
+                if let Some(me) = combatants.iter_mut().find(|c| c.entity == entity) { me.pos = Point::new(pos.x, pos.y); }
+            }
+        }
+
+        for (entity, _bystander, pos) in (&entities, &bystanders, &mut position).join() { /*Never attack anyone, just take a step in a random direction now and then*/
+            if !energy_system::try_act(speeds.get_mut(entity), self.round) { continue; }
+            let x = pos.x + rng.range(-1, 2);
+            let y = pos.y + rng.range(-1, 2);
+            if !map.in_bounds(x, y) || (x == player_pos.x && y == player_pos.y) { continue; } /*The player may have just moved there, before the index caught up*/
+            let next = map.xy_idx(x, y);
+            if map.blocked[next] || !map.tile_content[next].is_empty() { continue; } /*Don't walk into walls, and don't share a tile with anything*/
+            let current = map.xy_idx(pos.x, pos.y);
+            map.blocked[current] = false;
+            map.blocked[next] = true;
+            pos.x = x;
+            pos.y = y;
+            moved.single_write(MovedEvent { entity, x, y });
+            wade(&map, next, speeds.get_mut(entity));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use specs::prelude::*;
+use specs::shrev::EventChannel;
+use super::{Position, BlocksTile};
+
+/*Sent whenever something ends up on a new tile, by walking, being knocked back or swapping places. Only real moves are sent,
+so the systems listening only do work for what actually moved instead of going over everything every turn*/
+pub struct MovedEvent {
+    pub entity: Entity,
+    pub x: i32,
+    pub y: i32,
+}
+
+pub type MovedChannel = EventChannel<MovedEvent>;
+
+/*How far through the channel each listening system has read. Kept in the world, since the systems themselves are made fresh every turn.
+The indexing system also reads what the Position and BlocksTile storages send, to hear about things appearing, going away or no longer blocking*/
+pub struct MovedReaders {
+    pub visibility: ReaderId<MovedEvent>,
+    pub indexing: ReaderId<MovedEvent>,
+    pub triggers: ReaderId<MovedEvent>,
+    pub placed: ReaderId<ComponentEvent>,
+    pub blocking: ReaderId<ComponentEvent>,
+}
+
+pub fn register(ecs: &mut World) { /*After the components and before anything moves, since a reader only sees what was sent after it was registered*/
+    let mut channel = MovedChannel::new();
+    let readers = MovedReaders {
+        visibility: channel.register_reader(),
+        indexing: channel.register_reader(),
+        triggers: channel.register_reader(),
+        placed: ecs.write_storage::<Position>().register_reader(),
+        blocking: ecs.write_storage::<BlocksTile>().register_reader(),
+    };
+    ecs.insert(channel);
+    ecs.insert(readers);
+}
@@ -0,0 +1,109 @@
+use rltk::RGB;
+use specs::prelude::*;
+use super::{ParticleLifetime, FloatingText, BlinkUntil, Position, Renderable, Map, game_time::GameTime};
+
+pub const SPAWN_BLINK_MS : f32 = 1000.0; /*How long a new monster blinks for once the player can see it*/
+
+/*Counts down every particle's lifetime by the animation steps that passed this frame, and deletes the ones that have run out*/
+pub fn cull_dead_particles(ecs : &mut World) {
+    let elapsed_ms = ecs.fetch::<GameTime>().animation_ms();
+    let mut dead_particles : Vec<Entity> = Vec::new();
+    { /*Scoped so the storage is released before deleting*/
+        let mut particles = ecs.write_storage::<ParticleLifetime>();
+        let entities = ecs.entities();
+        for (entity, particle) in (&entities, &mut particles).join() {
+            particle.lifetime_ms -= elapsed_ms;
+            if particle.lifetime_ms < 0.0 {
+                dead_particles.push(entity);
+            }
+        }
+    }
+    for dead in dead_particles.iter() {
+        ecs.delete_entity(*dead).expect("Particle will not die");
+    }
+}
+
+/*Floating text works the same way, but it also drifts upwards and fades towards black as it runs out*/
+pub fn update_floating_text(ecs : &mut World) {
+    let elapsed_ms = ecs.fetch::<GameTime>().animation_ms();
+    let mut finished : Vec<Entity> = Vec::new();
+    {
+        let mut texts = ecs.write_storage::<FloatingText>();
+        let entities = ecs.entities();
+        for (entity, text) in (&entities, &mut texts).join() {
+            text.remaining_ms -= elapsed_ms;
+            text.offset_y += text.dy_per_ms * elapsed_ms;
+            if text.remaining_ms <= 0.0 {
+                finished.push(entity);
+            }
+        }
+    }
+    for done in finished.iter() {
+        ecs.delete_entity(*done).expect("Floating text will not die");
+    }
+}
+
+/*Blinking only counts down while the player can see it, so a monster that spawned out of sight still blinks when it is first spotted*/
+pub fn cull_blinking(ecs : &mut World) {
+    let elapsed_ms = ecs.fetch::<GameTime>().animation_ms();
+    let map = ecs.fetch::<Map>();
+    let positions = ecs.read_storage::<Position>();
+    let mut blinking = ecs.write_storage::<BlinkUntil>();
+    let mut finished : Vec<Entity> = Vec::new();
+    for (entity, blink, pos) in (&ecs.entities(), &mut blinking, &positions).join() {
+        if !map.visible_tiles[map.xy_idx(pos.x, pos.y)] { continue; }
+        blink.ms_remaining -= elapsed_ms;
+        if blink.ms_remaining <= 0.0 {
+            finished.push(entity);
+        }
+    }
+    for done in finished.iter() {
+        blinking.remove(*done);
+    }
+}
+
+struct ParticleRequest {
+    x: i32,
+    y: i32,
+    fg: RGB,
+    bg: RGB,
+    glyph: rltk::FontCharType,
+    lifetime: f32,
+}
+
+/*Systems can't create entities while they are running, so they queue particles up here and the spawn system makes them afterwards*/
+pub struct ParticleBuilder {
+    requests : Vec<ParticleRequest>,
+}
+
+impl ParticleBuilder {
+    pub fn new() -> ParticleBuilder {
+        ParticleBuilder { requests : Vec::new() }
+    }
+
+    pub fn request(&mut self, x: i32, y: i32, fg: RGB, bg: RGB, glyph: rltk::FontCharType, lifetime: f32) {
+        self.requests.push(ParticleRequest { x, y, fg, bg, glyph, lifetime });
+    }
+}
+
+pub struct ParticleSpawnSystem {}
+
+impl<'a> System<'a> for ParticleSpawnSystem { /*Turns the queued particle requests into real entities*/
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, Renderable>,
+                        WriteStorage<'a, ParticleLifetime>,
+                        WriteExpect<'a, ParticleBuilder>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut positions, mut renderables, mut particles, mut particle_builder) = data;
+        for new_particle in particle_builder.requests.iter() {
+            let p = entities.create();
+            positions.insert(p, Position { x: new_particle.x, y: new_particle.y }).expect("Unable to insert position");
+            renderables.insert(p, Renderable { fg: new_particle.fg, bg: new_particle.bg, glyph: new_particle.glyph, render_order: 0 }).expect("Unable to insert renderable");
+            particles.insert(p, ParticleLifetime { lifetime_ms: new_particle.lifetime }).expect("Unable to insert lifetime");
+        }
+
+        particle_builder.requests.clear();
+    }
+}
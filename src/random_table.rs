@@ -0,0 +1,70 @@
+use rltk::RandomNumberGenerator;
+
+pub struct RandomEntry {
+    name : String,
+    weight : i32,
+}
+
+/*A list of names, each with a weight. Rolling picks one, and an entry with twice the weight comes up twice as often*/
+#[derive(Default)]
+pub struct RandomTable {
+    entries : Vec<RandomEntry>,
+    total_weight : i32,
+}
+
+impl RandomTable {
+    pub fn new() -> RandomTable {
+        RandomTable { entries: Vec::new(), total_weight: 0 }
+    }
+
+    pub fn add<S : ToString>(mut self, name: S, weight: i32) -> RandomTable { /*Entries with no weight are left out, so they can never be picked*/
+        if weight > 0 {
+            self.total_weight += weight;
+            self.entries.push(RandomEntry { name: name.to_string(), weight });
+        }
+        self
+    }
+
+    /*Rolls a number under the total weight and walks the list until the running weights pass it. Empty tables give "None"*/
+    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> String {
+        if self.total_weight == 0 { return "None".to_string(); }
+        let mut roll = rng.roll_dice(1, self.total_weight) - 1;
+
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return entry.name.clone();
+            }
+            roll -= entry.weight;
+        }
+        "None".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_set_how_often_an_entry_comes_up() {
+        let table = RandomTable::new().add("Light", 1).add("Heavy", 3);
+        let mut rng = RandomNumberGenerator::seeded(42);
+        let rolls = 10000;
+        let heavy = (0..rolls).filter(|_| table.roll(&mut rng) == "Heavy").count();
+        let share = heavy as f32 / rolls as f32;
+        assert!((0.72..0.78).contains(&share), "Heavy came up {} of the time", share);
+    }
+
+    #[test]
+    fn an_empty_table_rolls_none() {
+        let mut rng = RandomNumberGenerator::seeded(1);
+        assert_eq!(RandomTable::new().roll(&mut rng), "None");
+    }
+
+    #[test]
+    fn zero_weight_entries_never_come_up() {
+        let mut rng = RandomNumberGenerator::seeded(7);
+        let table = RandomTable::new().add("Never", 0).add("Always", 2).add("Also never", -1);
+        assert!((0..1000).all(|_| table.roll(&mut rng) == "Always"));
+        assert_eq!(RandomTable::new().add("Never", 0).roll(&mut rng), "None");
+    }
+}
@@ -0,0 +1,201 @@
+use std::sync::Arc;
+use rltk::RGB;
+use serde::Deserialize;
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use super::{CombatStats, Monster, Name, Position, Renderable, Viewshed, BlocksTile, Item, ProvidesHealing, SerializeMe, Consumable, Ranged, InflictsDamage,
+    AreaOfEffect, Confusion, Equippable, EquipmentSlot, MeleePowerBonus, DefenseBonus, ProvidesFood, MagicMapper, ObfuscatedName, DamageType, Speed, LightSource,
+    Knockback, Faction, Charmed, Ammo, Charges, Poisonous, Size, Stackable, BlinkUntil, particle_system::SPAWN_BLINK_MS, factions::MONSTER_FACTION, dungeon::MasterDungeon,
+    difficulty::Difficulty, energy_system::NORMAL_THRESHOLD};
+use super::random_table::RandomTable;
+
+pub const SPAWNS_FILE : &str = "./spawns.ron";
+const BUILT_IN_SPAWNS : &str = include_str!("../spawns.ron"); /*Used when there is no spawns.ron next to the game, or it can't be read*/
+
+#[derive(Deserialize)]
+pub struct RawSpawn {
+    name : String,
+    weight : i32,
+    #[serde(default)]
+    per_depth : i32, /*Added to the weight once for each level down*/
+}
+
+#[derive(Deserialize)]
+pub struct RawStats {
+    hp : i32,
+    defense : i32,
+    power : i32,
+    #[serde(default)]
+    resistances : Vec<DamageType>,
+    #[serde(default)]
+    weaknesses : Vec<DamageType>,
+}
+
+fn one() -> i32 { 1 }
+
+#[derive(Deserialize)]
+pub struct RawMonster {
+    name : String,
+    glyph : char,
+    fg : (u8, u8, u8),
+    stats : RawStats,
+    #[serde(default = "one")]
+    moves_per_turn : i32,
+    poisonous : Option<Poisonous>,
+    size : Option<Size>,
+}
+
+/*Which made up names an item goes by until it is identified*/
+#[derive(Deserialize, PartialEq, Clone, Copy)]
+pub enum Disguise { Potion, Scroll }
+
+#[derive(Deserialize)]
+pub struct RawItem {
+    name : String,
+    glyph : char,
+    fg : (u8, u8, u8),
+    disguise : Option<Disguise>,
+    #[serde(default)]
+    consumable : bool,
+    #[serde(default)]
+    food : bool,
+    #[serde(default)]
+    magic_mapping : bool,
+    #[serde(default)]
+    stackable : bool,
+    equip : Option<EquipmentSlot>,
+    healing : Option<ProvidesHealing>,
+    ranged : Option<Ranged>,
+    damage : Option<InflictsDamage>,
+    area_of_effect : Option<AreaOfEffect>,
+    confusion : Option<Confusion>,
+    charm : Option<Charmed>,
+    melee_bonus : Option<MeleePowerBonus>,
+    defense_bonus : Option<DefenseBonus>,
+    knockback : Option<Knockback>,
+    ammo : Option<Ammo>,
+    charges : Option<i32>, /*Starts full*/
+    light : Option<LightSource>,
+}
+
+#[derive(Deserialize)]
+pub struct Raws {
+    spawn_table : Vec<RawSpawn>,
+    monsters : Vec<RawMonster>,
+    items : Vec<RawItem>,
+}
+
+/*Every monster and item the dungeon can spawn, as read from spawns.ron. Kept as a resource, and cheap to clone so it can be held
+while entities are being created*/
+#[derive(Clone)]
+pub struct RawMaster {
+    raws : Arc<Raws>,
+}
+
+impl RawMaster {
+    /*spawns.ron if there is one, otherwise the copy built into the game. A file that doesn't parse gets a warning and the built in copy*/
+    pub fn load() -> RawMaster {
+        if let Ok(contents) = std::fs::read_to_string(SPAWNS_FILE) {
+            match ron::de::from_str::<Raws>(&contents) {
+                Ok(raws) => return RawMaster { raws: Arc::new(raws) },
+                Err(e) => eprintln!("Warning: {} could not be read ({}), using the built in spawns", SPAWNS_FILE, e),
+            }
+        }
+        RawMaster::built_in()
+    }
+
+    pub fn built_in() -> RawMaster {
+        RawMaster { raws: Arc::new(ron::de::from_str(BUILT_IN_SPAWNS).expect("The built in spawns.ron is broken")) }
+    }
+
+    /*What can turn up in a room at this depth, and how likely it is*/
+    pub fn room_table(&self, depth: i32) -> RandomTable {
+        self.raws.spawn_table.iter().fold(RandomTable::new(), |table, spawn| table.add(&spawn.name, spawn.weight + spawn.per_depth * depth))
+    }
+
+    /*How much room whatever has this name takes up*/
+    pub fn size_of(&self, name: &str) -> Size {
+        self.raws.monsters.iter().find(|monster| monster.name == name).and_then(|monster| monster.size).unwrap_or(Size::SINGLE)
+    }
+
+    /*The name spawns.ron gives whatever is typed, ignoring case. Only the debug console needs this, everything else already has the exact name*/
+    #[cfg(feature = "debug")]
+    pub fn find_name(&self, typed: &str) -> Option<&str> {
+        self.raws.monsters.iter().map(|monster| monster.name.as_str())
+            .chain(self.raws.items.iter().map(|item| item.name.as_str()))
+            .find(|name| name.eq_ignore_ascii_case(typed))
+    }
+
+    /*The real names of every item that goes by this kind of made up name*/
+    pub fn disguised(&self, disguise: Disguise) -> Vec<&str> {
+        self.raws.items.iter().filter(|item| item.disguise == Some(disguise)).map(|item| item.name.as_str()).collect()
+    }
+}
+
+fn renderable(glyph: char, fg: (u8, u8, u8), render_order: i32) -> Renderable {
+    Renderable { glyph: rltk::to_cp437(glyph), fg: RGB::from_u8(fg.0, fg.1, fg.2), bg: RGB::named(rltk::BLACK), render_order }
+}
+
+/*Builds the monster or item with this name at pos. None if spawns.ron has nothing by that name*/
+pub fn spawn_named_entity(raws: &RawMaster, ecs: &mut World, name: &str, pos: Position) -> Option<Entity> {
+    if let Some(monster) = raws.raws.monsters.iter().find(|monster| monster.name == name) {
+        return Some(spawn_monster(ecs, monster, pos));
+    }
+    if let Some(item) = raws.raws.items.iter().find(|item| item.name == name) {
+        return Some(spawn_item(ecs, item, pos));
+    }
+    None
+}
+
+/*The stats in the file are for Normal, and get scaled by the difficulty*/
+fn spawn_monster(ecs: &mut World, raw: &RawMonster, pos: Position) -> Entity {
+    let difficulty = *ecs.fetch::<Difficulty>();
+    let max_hp = difficulty.monster_hp(raw.stats.hp);
+    let stats = CombatStats { max_hp, hp: max_hp, defense: raw.stats.defense, power: difficulty.monster_power(raw.stats.power),
+        resistances: raw.stats.resistances.clone(), weaknesses: raw.stats.weaknesses.clone() };
+    let mut builder = ecs.create_entity()
+        .with(pos)
+        .with(renderable(raw.glyph, raw.fg, 1))
+        .with(Viewshed { visible_tiles: Vec::new(), range: 8, dirty: true })
+        .with(Monster{ fleeing: false })
+        .with(Name { name: raw.name.clone() })
+        .with(Faction { name: MONSTER_FACTION.to_string() })
+        .with(BlocksTile{})
+        .with(Speed { energy: 0, threshold: NORMAL_THRESHOLD / i32::max(1, raw.moves_per_turn) })
+        .with(stats)
+        .with(BlinkUntil { ms_remaining: SPAWN_BLINK_MS });
+    if let Some(poisonous) = &raw.poisonous { builder = builder.with(poisonous.clone()); }
+    if let Some(size) = raw.size { builder = builder.with(size); }
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+fn spawn_item(ecs: &mut World, raw: &RawItem, pos: Position) -> Entity {
+    let obfuscated = raw.disguise.map(|_| { /*A dungeon from an old save has no made up names, so those items are known from the start*/
+        let dungeon = ecs.fetch::<MasterDungeon>();
+        ObfuscatedName { name: dungeon.obfuscated_name(&raw.name).cloned().unwrap_or_else(|| raw.name.clone()) }
+    });
+    let mut builder = ecs.create_entity()
+        .with(pos)
+        .with(renderable(raw.glyph, raw.fg, 2))
+        .with(Name { name: raw.name.clone() })
+        .with(Item{});
+    if let Some(obfuscated) = obfuscated { builder = builder.with(obfuscated); }
+    if raw.consumable { builder = builder.with(Consumable{}); }
+    if raw.food { builder = builder.with(ProvidesFood{}); }
+    if raw.magic_mapping { builder = builder.with(MagicMapper{}); }
+    if raw.stackable { builder = builder.with(Stackable{}); }
+    if let Some(slot) = raw.equip { builder = builder.with(Equippable { slot }); }
+    if let Some(healing) = &raw.healing { builder = builder.with(healing.clone()); }
+    if let Some(ranged) = &raw.ranged { builder = builder.with(ranged.clone()); }
+    if let Some(damage) = &raw.damage { builder = builder.with(damage.clone()); }
+    if let Some(area) = &raw.area_of_effect { builder = builder.with(area.clone()); }
+    if let Some(confusion) = &raw.confusion { builder = builder.with(confusion.clone()); }
+    if let Some(charm) = &raw.charm { builder = builder.with(charm.clone()); }
+    if let Some(bonus) = &raw.melee_bonus { builder = builder.with(bonus.clone()); }
+    if let Some(bonus) = &raw.defense_bonus { builder = builder.with(bonus.clone()); }
+    if let Some(knockback) = &raw.knockback { builder = builder.with(knockback.clone()); }
+    if let Some(ammo) = &raw.ammo { builder = builder.with(ammo.clone()); }
+    if let Some(charges) = raw.charges { builder = builder.with(Charges { current: charges, max: charges }); }
+    if let Some(light) = &raw.light { builder = builder.with(light.clone()); }
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
@@ -0,0 +1,486 @@
+use specs::prelude::*;
+use specs::saveload::{SimpleMarker, SimpleMarkerAllocator, SerializeComponents, DeserializeComponents, MarkedBuilder, MarkerAllocator};
+use specs::error::NoError;
+use std::fs;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use super::components::*;
+use super::{Map, gamelog::{GameLog, SAVED_ENTRIES}, stats::{Stats, PlayerName}, dungeon::{MasterDungeon, IdentifiedItems}, difficulty::Difficulty};
+
+pub const SAVE_SLOTS : u8 = 3;
+
+fn save_file(slot: u8) -> String { /*The first slot keeps the old file name, so a save from before there were slots turns up in it*/
+    if slot == 0 { "./savegame.json".to_string() } else { format!("./savegame{}.json", slot + 1) }
+}
+
+/*Bump this whenever the save layout changes. Saves one version behind are still loaded, anything older or newer is refused.
+Version 0 is a save from before there was a header at all, version 1 is from before BlocksVisibility, version 2 from before doors,
+version 3 from before magic mapping, version 4 from before unidentified items, version 5 from before speed, version 6 from before light sources, version 7 from before knockback,
+version 8 from before factions, version 9 from before charming, version 10 from before ammunition,
+version 11 from before poison, version 12 from before wand charges,
+version 13 from before big monsters, version 14 from before stacking*/
+pub const CURRENT_SAVE_VERSION : u32 = 15;
+
+#[derive(Serialize, Deserialize)]
+struct SaveHeader { /*Written before everything else in the save file*/
+    version: u32,
+}
+
+/*Why a save couldn't be loaded. These are shown on the main menu rather than crashing the game*/
+pub enum LoadError {
+    Unreadable(String),
+    IncompatibleVersion { found: u32, expected: u32 },
+    Corrupt(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Unreadable(reason) => write!(f, "Couldn't read the save: {}", reason),
+            LoadError::IncompatibleVersion { found, expected } => write!(f, "That save is version {}, this game needs {}", found, expected),
+            LoadError::Corrupt(reason) => write!(f, "The save is damaged: {}", reason),
+        }
+    }
+}
+
+/*specs can only serialize a fixed size tuple of storages at a time, so this writes each component type out one after the other.
+The marker picks which entities get written: SerializeMe for the save file, SerializeLevel for a level being put away*/
+macro_rules! serialize_individually {
+    ($ecs:expr, $ser:expr, $data:expr, $marker:ty, $( $type:ty),*) => {
+        $(
+        SerializeComponents::<NoError, SimpleMarker<$marker>>::serialize(
+            &( $ecs.read_storage::<$type>(), ),
+            &$data.0,
+            &$data.1,
+            &mut $ser,
+        )
+        .unwrap();
+        )*
+    };
+}
+
+/*The matching half of serialize_individually, reading from the json values the file was split into, in the same order.
+Types past the end of the values are left empty, which is how an older save without the newest components still loads. Stops at the first error*/
+macro_rules! deserialize_individually {
+    ($ecs:expr, $values:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        if let Some(value) = $values.next() {
+            DeserializeComponents::<NoError, _>::deserialize(
+                &mut ( &mut $ecs.write_storage::<$type>(), ),
+                &$data.0, /*entities*/
+                &mut $data.1, /*marker*/
+                &mut $data.2, /*allocater*/
+                value,
+            )?;
+        }
+        )*
+    };
+}
+
+macro_rules! count_types {
+    (, $( $type:ty),*) => { [$( stringify!($type) ),*].len() };
+}
+
+/*Every component that gets saved, in the order they are written. Both the save file and cached levels use this list, so they can't get out of step.
+New components go on the end, so older saves line up with the start of the list*/
+macro_rules! with_saved_components {
+    ($mac:ident!($($args:tt)*) $(, $extra:ty)*) => {
+        $mac!($($args)*, Position, Renderable, LeftMover, Player, Viewshed, CombatStats,
+            WantsToMelee, SufferDamage, Monster, Bystander, BlocksTile, Name, Item, ProvidesHealing, InBackpack,
+            WantsToPickupItem, WantsToUseItem, WantsToDropItem, Consumable, Ranged, InflictsDamage, AreaOfEffect, Confusion,
+            Equippable, Equipped, MeleePowerBonus, DefenseBonus, HungerClock, ProvidesFood,
+            Hidden, EntryTrigger, SingleActivation, BlocksVisibility, Door, MagicMapper, ObfuscatedName, Speed, LightSource, Knockback, Faction, Charmed, Ammo, DamageOverTime, Poisonous, Charges, Size, Stackable $(, $extra)*)
+    };
+}
+
+/*Components that are deliberately never saved, because they only last a turn or a few frames. Anything not in one of these two lists doesn't get registered,
+so a new component can't be added without deciding whether it is saved*/
+macro_rules! with_unsaved_components {
+    ($mac:ident!($($args:tt)*)) => {
+        $mac!($($args)*, ParticleLifetime, FloatingText, BlinkUntil)
+    };
+}
+
+macro_rules! register_individually {
+    ($ecs:expr, $( $type:ty),*) => {
+        $( $ecs.register::<$type>(); )*
+    };
+}
+
+/*Registers every component an entity can have, straight from the lists above, plus the save markers and the helper entity's component*/
+pub fn register_components(ecs: &mut World) {
+    with_saved_components!(register_individually!(ecs));
+    with_unsaved_components!(register_individually!(ecs));
+    ecs.register::<SimpleMarker<SerializeMe>>();
+    ecs.register::<SimpleMarker<SerializeLevel>>();
+    ecs.register::<SerializationHelper>();
+}
+
+/*Writes every marked entity, plus the map, to the slot's save file*/
+pub fn save_game(ecs : &mut World, slot: u8) {
+    let writer = File::create(save_file(slot)).unwrap();
+    save_to(ecs, writer);
+}
+
+/*Does the actual saving, into anything that can be written to. save_game hands it the save file*/
+pub fn save_to<W: Write>(ecs : &mut World, writer: W) {
+    let mapcopy = ecs.get_mut::<Map>().unwrap().clone(); /*The map and the run stats ride along on a temporary entity*/
+    let statscopy = ecs.get_mut::<Stats>().unwrap().clone();
+    let dungeoncopy = ecs.get_mut::<MasterDungeon>().unwrap().clone();
+    let identifiedcopy = ecs.get_mut::<IdentifiedItems>().unwrap().clone();
+    let difficulty = *ecs.fetch::<Difficulty>();
+    let rngcopy = ecs.get_mut::<rltk::RandomNumberGenerator>().unwrap().clone();
+    let namecopy = ecs.get_mut::<PlayerName>().unwrap().clone();
+    let logcopy = ecs.fetch::<GameLog>().newest(SAVED_ENTRIES);
+    let savehelper = ecs
+        .create_entity()
+        .with(SerializationHelper { map: mapcopy, stats: statscopy, dungeon: dungeoncopy, identified: identifiedcopy, difficulty, rng: Some(rngcopy), name: namecopy, log: logcopy })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    {
+        let data = ( ecs.entities(), ecs.read_storage::<SimpleMarker<SerializeMe>>() );
+        let mut serializer = serde_json::Serializer::new(writer);
+        SaveHeader { version: CURRENT_SAVE_VERSION }.serialize(&mut serializer).unwrap();
+        with_saved_components!(serialize_individually!(ecs, serializer, data, SerializeMe), SerializationHelper);
+    }
+
+    ecs.delete_entity(savehelper).expect("Crash on cleanup");
+}
+
+pub fn does_save_exist(slot: u8) -> bool {
+    Path::new(&save_file(slot)).exists()
+}
+
+pub fn delete_save(slot: u8) -> io::Result<()> {
+    match fs::remove_file(save_file(slot)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()), /*Already gone, which is all deleting it was for*/
+        result => result,
+    }
+}
+
+/*What the main menu shows for a slot that has a save in it*/
+#[derive(Clone, Copy)]
+pub struct SlotSummary {
+    pub depth: i32,
+    pub turns: i32,
+}
+
+/*Reads the depth and turn count back out of a slot without loading it. They live on the SerializationHelper at the end of the file.
+None if the slot is empty, or if the save can't be made sense of*/
+pub fn slot_summary(slot: u8) -> Option<SlotSummary> {
+    let data = fs::read_to_string(save_file(slot)).ok()?;
+    let (_version, start) = read_version(&data);
+    let helper = split_values(&data[start..]).ok()?.pop()?;
+    let saved = helper.as_array()?.iter()
+        .filter_map(|entity| entity.pointer("/components/0"))
+        .find(|component| !component.is_null())?;
+    Some(SlotSummary {
+        depth: saved.pointer("/map/depth")?.as_i64()? as i32,
+        turns: saved.pointer("/stats/turns").and_then(|turns| turns.as_i64()).unwrap_or(0) as i32, /*Saves from before the stats were kept have no turn count*/
+    })
+}
+
+pub fn slot_summaries() -> Vec<Option<SlotSummary>> {
+    (0..SAVE_SLOTS).map(slot_summary).collect()
+}
+
+/*Works out which version a save is from. Saves from before the header existed start straight in on the components*/
+fn read_version(data: &str) -> (u32, usize) { /*The version, and how far into the file the components start*/
+    let mut stream = serde_json::Deserializer::from_str(data).into_iter::<SaveHeader>();
+    match stream.next() {
+        Some(Ok(header)) => (header.version, stream.byte_offset()),
+        _ => (0, 0),
+    }
+}
+
+fn split_values(data: &str) -> Result<Vec<serde_json::Value>, serde_json::Error> { /*One value per component type, as written by serialize_individually*/
+    serde_json::Deserializer::from_str(data).into_iter::<serde_json::Value>().collect()
+}
+
+fn read_components(ecs: &mut World, components: Vec<serde_json::Value>, helper: serde_json::Value) -> Result<(), serde_json::Error> {
+    let mut d = (&mut ecs.entities(), &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(), &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>());
+    let mut values = components.into_iter();
+    with_saved_components!(deserialize_individually!(ecs, values, d));
+    let mut values = std::iter::once(helper); /*Always last in the file, however many components came before it*/
+    deserialize_individually!(ecs, values, d, SerializationHelper);
+    Ok(())
+}
+
+/*Throws away the current world and replaces it with whatever is in the save file. The version and the json are checked first,
+so a save that can't be used leaves the current game alone. If it still goes wrong part way through, the world is left empty*/
+pub fn load_game(ecs: &mut World, slot: u8) -> Result<(), LoadError> {
+    let data = fs::read_to_string(save_file(slot)).map_err(|e| LoadError::Unreadable(e.to_string()))?;
+    load_from(ecs, &data)
+}
+
+/*The other half of save_to, loading from a save that has already been read in*/
+pub fn load_from(ecs: &mut World, data: &str) -> Result<(), LoadError> {
+    let (version, start) = read_version(data);
+    if version > CURRENT_SAVE_VERSION || version + 1 < CURRENT_SAVE_VERSION { /*One version behind is migrated by the serde defaults and missing storages*/
+        return Err(LoadError::IncompatibleVersion { found: version, expected: CURRENT_SAVE_VERSION });
+    }
+    let mut components = split_values(&data[start..]).map_err(|e| LoadError::Corrupt(e.to_string()))?;
+    let helper = components.pop().ok_or_else(|| LoadError::Corrupt("the file is empty".to_string()))?;
+    let expected = with_saved_components!(count_types!());
+    if components.len() > expected || (version == CURRENT_SAVE_VERSION && components.len() != expected) {
+        return Err(LoadError::Corrupt(format!("expected {} kinds of component, found {}", expected, components.len())));
+    }
+
+    { /*Clear out everything that exists right now*/
+        let mut to_delete = Vec::new();
+        for e in ecs.entities().join() {
+            to_delete.push(e);
+        }
+        for del in to_delete.iter() {
+            ecs.delete_entity(*del).expect("Deletion failed");
+        }
+    }
+
+    if let Err(e) = read_components(ecs, components, helper) {
+        ecs.delete_all();
+        ecs.remove::<Entity>(); /*Without a player the menu knows there is no game to go back to*/
+        return Err(LoadError::Corrupt(e.to_string()));
+    }
+
+    let mut deleteme : Option<Entity> = None;
+    let mut loaded_map : Option<Map> = None;
+    let mut loaded_stats = Stats::default();
+    let mut loaded_dungeon = MasterDungeon::default();
+    let mut loaded_identified = IdentifiedItems::default();
+    let mut loaded_difficulty = Difficulty::default();
+    let mut loaded_rng = None;
+    let mut loaded_name = PlayerName::default();
+    let mut loaded_log = Vec::new();
+    let mut loaded_player : Option<(Entity, rltk::Point)> = None;
+    { /*Put the loaded map back in place, and point the player resources at the loaded player*/
+        let entities = ecs.entities();
+        let helper = ecs.read_storage::<SerializationHelper>();
+        let player = ecs.read_storage::<Player>();
+        let position = ecs.read_storage::<Position>();
+        for (e, h) in (&entities, &helper).join() {
+            loaded_map = Some(h.map.clone());
+            loaded_stats = h.stats.clone();
+            loaded_dungeon = h.dungeon.clone();
+            loaded_identified = h.identified.clone();
+            loaded_difficulty = h.difficulty;
+            loaded_rng = h.rng.clone();
+            loaded_name = h.name.clone();
+            loaded_log = h.log.clone();
+            deleteme = Some(e);
+        }
+        for (e, _p, pos) in (&entities, &player, &position).join() {
+            loaded_player = Some((e, rltk::Point::new(pos.x, pos.y)));
+        }
+    }
+    let (Some(helper), Some(mut worldmap), Some((player_entity, player_pos))) = (deleteme, loaded_map, loaded_player) else {
+        ecs.delete_all();
+        ecs.remove::<Entity>();
+        return Err(LoadError::Corrupt("no map or no player".to_string()));
+    };
+    ecs.delete_entity(helper).expect("Unable to delete helper");
+
+    /*Inserting replaces whatever was there, and also works when loading straight from the main menu before any game exists*/
+    worldmap.tile_content = vec![Vec::new(); worldmap.tiles.len()]; /*Wasn't saved, the indexing system fills it back in*/
+    ecs.insert(worldmap);
+    ecs.insert(loaded_stats);
+    ecs.insert(loaded_dungeon);
+    ecs.insert(loaded_identified);
+    ecs.insert(loaded_difficulty);
+    if let Some(rng) = loaded_rng { ecs.insert(rng); }
+    ecs.insert(loaded_name);
+    ecs.insert(GameLog { entries: loaded_log.into() });
+    ecs.insert(player_entity);
+    ecs.insert(player_pos);
+    Ok(())
+}
+
+/*Writes the given entities out the same way the save file does, and hands back the json. Anything that isn't normally saved, like particles, is left out*/
+pub fn serialize_level(ecs: &mut World, level_entities: &[Entity]) -> String {
+    {
+        let save_markers = ecs.read_storage::<SimpleMarker<SerializeMe>>();
+        let mut level_markers = ecs.write_storage::<SimpleMarker<SerializeLevel>>();
+        let mut allocator = ecs.write_resource::<SimpleMarkerAllocator<SerializeLevel>>();
+        for entity in level_entities.iter().filter(|entity| save_markers.get(**entity).is_some()) {
+            allocator.mark(*entity, &mut level_markers);
+        }
+    }
+
+    let mut writer = Vec::new();
+    {
+        let data = ( ecs.entities(), ecs.read_storage::<SimpleMarker<SerializeLevel>>() );
+        let mut serializer = serde_json::Serializer::new(&mut writer);
+        with_saved_components!(serialize_individually!(ecs, serializer, data, SerializeLevel));
+    }
+
+    ecs.write_storage::<SimpleMarker<SerializeLevel>>().clear(); /*The markers were only needed to pick out this level's entities*/
+    String::from_utf8(writer).expect("Level json is not valid utf8")
+}
+
+/*Brings back the entities written by serialize_level. They get fresh save markers so the save file picks them up again*/
+pub fn deserialize_level(ecs: &mut World, level: &str) {
+    let values = split_values(level).expect("Cached level is not valid json"); /*Written by this same run, so it can't be from another version*/
+    read_level_components(ecs, values).expect("Cached level is not valid");
+
+    {
+        let entities = ecs.entities();
+        let level_markers = ecs.read_storage::<SimpleMarker<SerializeLevel>>();
+        let mut save_markers = ecs.write_storage::<SimpleMarker<SerializeMe>>();
+        let mut allocator = ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>();
+        for (entity, _marker) in (&entities, &level_markers).join() {
+            allocator.mark(entity, &mut save_markers);
+        }
+    }
+
+    ecs.write_storage::<SimpleMarker<SerializeLevel>>().clear();
+}
+
+fn read_level_components(ecs: &mut World, components: Vec<serde_json::Value>) -> Result<(), serde_json::Error> {
+    let mut values = components.into_iter();
+    let mut d = (&mut ecs.entities(), &mut ecs.write_storage::<SimpleMarker<SerializeLevel>>(), &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeLevel>>());
+    with_saved_components!(deserialize_individually!(ecs, values, d));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rltk::RandomNumberGenerator;
+    use specs::shred::MetaTable;
+    use specs::storage::AnyStorage;
+    use crate::{map::new_map, spawner, raws::RawMaster};
+
+    /*Counts how many entities have each saved component, in the order of with_saved_components*/
+    macro_rules! count_each {
+        ($ecs:expr, $( $type:ty),*) => {
+            vec![$( ($ecs.read_storage::<$type>().join().count(), stringify!($type)) ),*]
+        };
+    }
+
+    #[test]
+    fn every_component_is_registered_and_every_saved_one_is_written() {
+        let saved = with_saved_components!(count_types!());
+        let unsaved = with_unsaved_components!(count_types!());
+        let declared = include_str!("components.rs").matches("#[derive(Component").count();
+        assert_eq!(declared, saved + unsaved + 1, "a component in components.rs is missing from both lists"); /*The one extra is SerializationHelper*/
+
+        let ecs = empty_world();
+        let registered = ecs.fetch::<MetaTable<dyn AnyStorage>>().iter(&ecs).count();
+        assert_eq!(registered, saved + unsaved + 3); /*Plus the two save markers and the helper*/
+
+        let mut ecs = populated_world();
+        let mut file = Vec::new();
+        save_to(&mut ecs, &mut file);
+        let file = std::str::from_utf8(&file).expect("Save is not utf8");
+        let (_version, start) = read_version(file);
+        let written = split_values(&file[start..]).expect("Save is not valid json").len();
+        assert_eq!(written, saved + 1); /*Each saved component, then the helper*/
+    }
+
+    fn empty_world() -> World { /*What a load starts from: the components registered, and a marker allocator to hand out ids*/
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(SimpleMarkerAllocator::<SerializeLevel>::new());
+        ecs
+    }
+
+    fn populated_world() -> World { /*A first level with the player and whatever the spawn tables put in it*/
+        let mut ecs = empty_world();
+        ecs.insert(Difficulty::default());
+        ecs.insert(RawMaster::built_in());
+        ecs.insert(Stats::default());
+        ecs.insert(MasterDungeon::default());
+        ecs.insert(IdentifiedItems::default());
+        ecs.insert(PlayerName::new("Tester"));
+        ecs.insert(GameLog::new("Welcome"));
+        let mut rng = RandomNumberGenerator::seeded(3);
+        let (map, rooms) = new_map(&mut rng, 1, 80, 50);
+        ecs.insert(rng);
+        ecs.insert(map);
+        let (player_x, player_y) = rooms[0].center();
+        let player = spawner::player(&mut ecs, player_x, player_y);
+        ecs.insert(player);
+        ecs.insert(rltk::Point::new(player_x, player_y));
+        spawner::populate_level(&mut ecs, &rooms);
+        ecs
+    }
+
+    fn save_and_load(ecs: &mut World) -> World {
+        save_and_load_into(ecs, |_loaded| {})
+    }
+
+    fn save_and_load_into(ecs: &mut World, prepare: impl FnOnce(&mut World)) -> World { /*prepare sets up the world being loaded into, like a game already going*/
+        let mut saved = Vec::new();
+        save_to(ecs, &mut saved);
+        let mut loaded = empty_world();
+        prepare(&mut loaded);
+        assert!(load_from(&mut loaded, std::str::from_utf8(&saved).expect("Save is not utf8")).is_ok());
+        loaded
+    }
+
+    #[test]
+    fn a_saved_world_loads_back_the_same() {
+        let mut ecs = populated_world();
+        {
+            let player = *ecs.fetch::<Entity>();
+            let mut stats = ecs.write_storage::<CombatStats>();
+            stats.get_mut(player).expect("Player has no stats").hp -= 7; /*So the test isn't just checking the starting values*/
+            let mut map = ecs.write_resource::<Map>();
+            for revealed in map.revealed_tiles.iter_mut().step_by(3) { *revealed = true; }
+        }
+        let loaded = save_and_load(&mut ecs);
+
+        let stats_of = |ecs: &World| {
+            let player = *ecs.fetch::<Entity>();
+            let stats = ecs.read_storage::<CombatStats>();
+            let stats = stats.get(player).expect("Player has no stats");
+            (stats.hp, stats.max_hp, stats.defense, stats.power)
+        };
+        assert_eq!(stats_of(&loaded), stats_of(&ecs));
+        assert_eq!(*loaded.fetch::<rltk::Point>(), *ecs.fetch::<rltk::Point>());
+
+        let (map, loaded_map) = (ecs.fetch::<Map>(), loaded.fetch::<Map>());
+        assert!(loaded_map.tiles == map.tiles);
+        assert_eq!(loaded_map.revealed_tiles, map.revealed_tiles);
+        assert_eq!((loaded_map.width, loaded_map.height, loaded_map.depth), (map.width, map.height, map.depth));
+        assert_eq!(loaded_map.tile_content.len(), map.tiles.len());
+
+        let (before, after) = (with_saved_components!(count_each!(ecs)), with_saved_components!(count_each!(loaded)));
+        assert!(before.iter().any(|(count, name)| *name == "Monster" && *count > 0), "The level has nothing in it to save");
+        assert_eq!(after, before);
+    }
+
+    fn log_texts(log: &[crate::gamelog::LogEntry]) -> Vec<String> {
+        log.iter().map(|entry| entry.fragments.iter().map(|f| f.text.as_str()).collect::<Vec<_>>().join(" ")).collect()
+    }
+
+    #[test]
+    fn the_message_log_survives_a_save() {
+        let mut ecs = populated_world();
+        for message in ["You hit the goblin.", "The goblin hits you.", "The goblin is dead."] {
+            ecs.write_resource::<GameLog>().add(message);
+        }
+        let loaded = save_and_load(&mut ecs);
+        let texts = log_texts(&loaded.fetch::<GameLog>().newest(usize::MAX));
+        assert_eq!(texts[texts.len() - 3..], ["You hit the goblin.", "The goblin hits you.", "The goblin is dead."]);
+        assert_eq!(texts, log_texts(&ecs.fetch::<GameLog>().newest(usize::MAX)));
+    }
+
+    #[test]
+    fn only_the_newest_messages_are_saved() {
+        let mut ecs = populated_world();
+        for turn in 0..SAVED_ENTRIES + 10 {
+            ecs.write_resource::<GameLog>().add(format!("Turn {}", turn));
+        }
+        let newest = ecs.fetch::<GameLog>().newest(SAVED_ENTRIES);
+        assert_eq!(newest.len(), SAVED_ENTRIES);
+        let loaded = save_and_load(&mut ecs);
+        let texts = log_texts(&loaded.fetch::<GameLog>().newest(usize::MAX));
+        assert_eq!(texts, log_texts(&newest));
+        assert_eq!(texts.last().map(String::as_str), Some(format!("Turn {}", SAVED_ENTRIES + 9).as_str()));
+    }
+}
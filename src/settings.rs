@@ -0,0 +1,81 @@
+use super::{MAPWIDTH, MAPHEIGHT, gui::PANEL_HEIGHT};
+
+pub const SETTINGS_FILE : &str = "./settings.toml";
+
+const DEFAULT_COLUMNS : i32 = 80;
+const DEFAULT_ROWS : i32 = 50;
+const MIN_COLUMNS : i32 = 80; /*Anything narrower and the menus and the panel stop fitting*/
+const MIN_ROWS : i32 = 40;
+
+/*A font other than the built in one. The file is looked for in the resources folder, and the size is how big one character is in pixels*/
+pub struct Font {
+    pub file : String,
+    pub width : u32,
+    pub height : u32,
+}
+
+/*How big the window is in characters and what it is drawn with. Stored as a resource so new levels can be sized to fit*/
+pub struct Settings {
+    pub columns : i32,
+    pub rows : i32,
+    pub font : Option<Font>,
+}
+
+impl Settings {
+    pub fn defaults() -> Settings {
+        Settings { columns: DEFAULT_COLUMNS, rows: DEFAULT_ROWS, font: None }
+    }
+
+    /*Levels are never smaller than the usual size, but a big window gets a map that fills it*/
+    pub fn map_size(&self) -> (i32, i32) {
+        (i32::max(MAPWIDTH, self.columns), i32::max(MAPHEIGHT, self.rows - PANEL_HEIGHT))
+    }
+
+    /*Read the same way as keys.toml: lines like columns = 100, with # starting a comment. A missing file just means the defaults.
+    The font is optional, font = "vga8x16.jpg" with font_width and font_height giving the size of a character (8 by 8 if left out)*/
+    pub fn load() -> Settings {
+        let mut settings = Settings::defaults();
+        let contents = match std::fs::read_to_string(SETTINGS_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return settings,
+        };
+
+        let (mut font_file, mut font_width, mut font_height) = (None, 8, 8);
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() || line.starts_with('[') { continue; }
+
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim().trim_matches('"')),
+                None => { eprintln!("Warning: {} line {} is not of the form name = value", SETTINGS_FILE, line_number + 1); continue; }
+            };
+            let number = || match value.parse::<u32>() {
+                Ok(number) if number > 0 => Some(number),
+                _ => { eprintln!("Warning: {} line {} wants a positive number for {}, not {}", SETTINGS_FILE, line_number + 1, name, value); None }
+            };
+            match name {
+                "columns" => if let Some(n) = number() { settings.columns = n as i32 },
+                "rows" => if let Some(n) = number() { settings.rows = n as i32 },
+                "font" => font_file = Some(value.to_string()),
+                "font_width" => if let Some(n) = number() { font_width = n },
+                "font_height" => if let Some(n) = number() { font_height = n },
+                _ => eprintln!("Warning: {} line {} has unknown setting {}", SETTINGS_FILE, line_number + 1, name),
+            }
+        }
+
+        if settings.columns < MIN_COLUMNS || settings.rows < MIN_ROWS { /*Too small to play in, so ignore the size rather than draw the UI off the edge*/
+            eprintln!("Warning: {} asks for {}x{} but the window must be at least {}x{}, using {}x{}", SETTINGS_FILE,
+                settings.columns, settings.rows, MIN_COLUMNS, MIN_ROWS, DEFAULT_COLUMNS, DEFAULT_ROWS);
+            settings.columns = DEFAULT_COLUMNS;
+            settings.rows = DEFAULT_ROWS;
+        }
+        if let Some(file) = font_file {
+            if std::path::Path::new("resources").join(&file).exists() {
+                settings.font = Some(Font { file, width: font_width, height: font_height });
+            } else {
+                eprintln!("Warning: {} names font {} but there is no such file in resources, using the built in font", SETTINGS_FILE, file);
+            }
+        }
+        settings
+    }
+}
@@ -0,0 +1,241 @@
+use rltk::{RGB, RandomNumberGenerator};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use super::{CombatStats, Monster, Bystander, Name, Position, Renderable, Viewshed, BlocksTile, Rect, Player, SerializeMe, Map, HungerClock, HungerState,
+    hunger_system::WELL_FED_TURNS, Hidden, EntryTrigger, SingleActivation, Door, BlocksVisibility, InflictsDamage, DamageType, Speed, Faction,
+    factions::PLAYER_FACTION, difficulty::Difficulty, energy_system::NORMAL_THRESHOLD, raws::{RawMaster, spawn_named_entity}};
+
+/*Creates the player and returns their entity*/
+pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
+    let max_hp = ecs.fetch::<Difficulty>().player_hp(30);
+    ecs.create_entity()
+        .with(Position { x: player_x, y: player_y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::named(rltk::YELLOW),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 0,
+        })
+        .with(Player{})
+        .with(Viewshed { visible_tiles: Vec::new(), range: 8, dirty: true })
+        .with(CombatStats { max_hp, hp: max_hp, defense: 2, power: 5, resistances: Vec::new(), weaknesses: Vec::new() })
+        .with(HungerClock { state: HungerState::WellFed, duration: WELL_FED_TURNS })
+        .with(Name { name: "Player".to_string() })
+        .with(Faction { name: PLAYER_FACTION.to_string() })
+        .marked::<SimpleMarker<SerializeMe>>() /*Include this entity when saving*/
+        .build()
+}
+
+const MAX_SPAWNS : i32 = 4;
+const MONSTER_CAP_BASE : usize = 20;
+const MONSTER_CAP_PER_DEPTH : usize = 2;
+
+/*The most monsters a freshly made level can start with. Spawn tables get busier deeper down, and without a limit a deep level floods*/
+pub fn monster_cap(depth: i32) -> usize {
+    MONSTER_CAP_BASE + MONSTER_CAP_PER_DEPTH * depth.max(0) as usize
+}
+
+/*Fills every room but the first, where the player arrives, then adds the doors and trims the monsters back to the cap for the depth*/
+pub fn populate_level(ecs: &mut World, rooms: &[Rect]) {
+    for room in rooms.iter().skip(1) {
+        spawn_room(ecs, room);
+    }
+    spawn_doors(ecs, rooms);
+    let depth = ecs.fetch::<Map>().depth;
+    cap_monsters(ecs, monster_cap(depth), rooms[0].center());
+}
+
+/*Deletes monsters until there are no more than cap, starting with whichever are farthest from arrival, so the ones near the player are kept*/
+pub fn cap_monsters(ecs: &mut World, cap: usize, arrival: (i32, i32)) {
+    let excess : Vec<Entity> = {
+        let monsters = ecs.read_storage::<Monster>();
+        let positions = ecs.read_storage::<Position>();
+        let arrival = rltk::Point::new(arrival.0, arrival.1);
+        let mut by_distance : Vec<(Entity, f32)> = (&ecs.entities(), &monsters, &positions).join()
+            .map(|(entity, _monster, pos)| (entity, rltk::DistanceAlg::PythagorasSquared.distance2d(arrival, rltk::Point::new(pos.x, pos.y))))
+            .collect();
+        by_distance.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        by_distance.into_iter().skip(cap).map(|(entity, _distance)| entity).collect()
+    };
+    for entity in excess {
+        ecs.delete_entity(entity).expect("Unable to delete entity");
+    }
+}
+
+/*Picks a handful of random floor tiles in the room and rolls the spawn table for each one. Deeper levels get a few more spawns per room*/
+pub fn spawn_room(ecs: &mut World, room : &Rect) {
+    let mut spawn_points : Vec<(usize, String)> = Vec::new();
+    let mut taken : Vec<usize> = Vec::new(); /*Every tile something is already spawning on*/
+
+    let raws = RawMaster::clone(&ecs.fetch::<RawMaster>()); /*Our own handle on it, so the world is free to create entities*/
+
+    { /*Scoped so the borrow of the rng resource ends before we start creating entities*/
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let map = ecs.fetch::<Map>();
+        let spawn_table = raws.room_table(map.depth);
+        let num_spawns = rng.roll_dice(1, MAX_SPAWNS + 3) + (map.depth - 1) - 3 + ecs.fetch::<Difficulty>().extra_spawns();
+
+        for _i in 0..num_spawns {
+            let mut added = false;
+            let mut tries = 0;
+            while !added && tries < 20 { /*Keep rolling until we find a tile nobody else is spawning on, but don't loop forever in a full room*/
+                let x = room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1));
+                let y = room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1));
+                let name = spawn_table.roll(&mut rng);
+                let footprint = raws.size_of(&name).tiles(x, y); /*Something big needs room for all of itself*/
+                let fits = footprint.iter().all(|(tile_x, tile_y)| map.in_bounds(*tile_x, *tile_y)
+                    && !map.tiles[map.xy_idx(*tile_x, *tile_y)].blocks_movement() /*Nothing gets spawned into walls, deep water or lava*/
+                    && !taken.contains(&map.xy_idx(*tile_x, *tile_y)));
+                if fits {
+                    taken.extend(footprint.iter().map(|(tile_x, tile_y)| map.xy_idx(*tile_x, *tile_y)));
+                    spawn_points.push((map.xy_idx(x, y), name));
+                    added = true;
+                }
+                tries += 1;
+            }
+        }
+    }
+
+    for (idx, name) in spawn_points.iter() {
+        let (x, y) = ecs.fetch::<Map>().idx_xy(*idx);
+        match name.as_ref() { /*The only two that aren't in spawns.ron*/
+            "Peasant" => peasant(ecs, x, y),
+            "Bear Trap" => bear_trap(ecs, x, y),
+            name => { spawn_named_entity(&raws, ecs, name, Position { x, y }); }
+        }
+    }
+}
+
+/*A harmless wanderer. It gets in the way, but it has no stats, so nothing can fight it and it never fights back*/
+fn peasant(ecs: &mut World, x: i32, y: i32) {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('p'),
+            fg: RGB::named(rltk::WHEAT),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Name { name: "Peasant".to_string() })
+        .with(Bystander{})
+        .with(Speed { energy: 0, threshold: NORMAL_THRESHOLD * 2 }) /*In no hurry*/
+        .with(BlocksTile{})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/*Hidden until it's found or stepped on, and it only snaps shut once*/
+fn bear_trap(ecs: &mut World, x: i32, y: i32) {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::RED),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name { name: "Bear Trap".to_string() })
+        .with(Hidden{})
+        .with(EntryTrigger{})
+        .with(InflictsDamage { damage: 6, damage_type: DamageType::Physical })
+        .with(SingleActivation{})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/*Puts a closed door in every gap where a corridor meets a room*/
+pub fn spawn_doors(ecs: &mut World, rooms: &[Rect]) {
+    let spots = ecs.fetch::<Map>().door_spots(rooms);
+    for (x, y) in spots {
+        door(ecs, x, y);
+    }
+}
+
+fn door(ecs: &mut World, x: i32, y: i32) {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('+'),
+            fg: RGB::named(rltk::CHOCOLATE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name { name: "Door".to_string() })
+        .with(Door { open: false })
+        .with(BlocksTile{})
+        .with(BlocksVisibility{})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::{map::new_map, saveload_system::register_components, dungeon::{MasterDungeon, IdentifiedItems}};
+    use specs::saveload::SimpleMarkerAllocator;
+
+    fn level_world(depth: i32, width: i32, height: i32) -> (World, Vec<Rect>) { /*A freshly dug level with nothing on it yet*/
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(Difficulty::Hard); /*Busier rooms, so there is plenty to cap*/
+        ecs.insert(RawMaster::built_in());
+        ecs.insert(MasterDungeon::default());
+        ecs.insert(IdentifiedItems::default());
+        let mut rng = RandomNumberGenerator::seeded(11);
+        let (map, rooms) = new_map(&mut rng, depth, width, height);
+        ecs.insert(rng);
+        ecs.insert(map);
+        (ecs, rooms)
+    }
+
+    fn monster_count(ecs: &World) -> usize {
+        ecs.read_storage::<Monster>().join().count()
+    }
+
+    #[test]
+    fn a_new_level_never_has_more_monsters_than_its_cap() {
+        let mut over_cap = 0;
+        for (depth, width, height) in [(1, 80, 50), (10, 80, 50), (10, 240, 150), (40, 240, 150)] { /*The big maps have rooms enough to go over*/
+            let (mut uncapped, rooms) = level_world(depth, width, height);
+            for room in rooms.iter().skip(1) {
+                spawn_room(&mut uncapped, room);
+            }
+            let spawned = monster_count(&uncapped);
+            if spawned > monster_cap(depth) { over_cap += 1; }
+
+            let (mut ecs, rooms) = level_world(depth, width, height);
+            populate_level(&mut ecs, &rooms);
+            ecs.maintain();
+            assert_eq!(monster_count(&ecs), usize::min(spawned, monster_cap(depth)), "depth {} on a {}x{} map", depth, width, height);
+        }
+        assert!(over_cap > 0, "no level spawned enough to need capping");
+    }
+
+    #[test]
+    fn capping_keeps_the_closest_monsters() {
+        let (mut ecs, rooms) = level_world(6, 80, 50);
+        for room in rooms.iter().skip(1) {
+            spawn_room(&mut ecs, room);
+        }
+
+        let arrival = rooms[0].center();
+        let distance = |pos: &Position| (pos.x - arrival.0).pow(2) + (pos.y - arrival.1).pow(2);
+        let distances = |ecs: &World| -> HashMap<Entity, i32> {
+            (&ecs.entities(), &ecs.read_storage::<Monster>(), &ecs.read_storage::<Position>()).join()
+                .map(|(entity, _monster, pos)| (entity, distance(pos)))
+                .collect()
+        };
+        let before = distances(&ecs);
+        assert!(before.len() > 10, "only {} monsters spawned, the cap has nothing to do", before.len());
+
+        cap_monsters(&mut ecs, 10, arrival);
+        ecs.maintain();
+        let after = distances(&ecs);
+        assert_eq!(after.len(), 10);
+        let farthest_kept = after.values().max().copied().unwrap_or(0);
+        let closest_removed = before.iter().filter(|(entity, _d)| !after.contains_key(entity)).map(|(_entity, d)| *d).min().unwrap_or(i32::MAX);
+        assert!(farthest_kept <= closest_removed, "kept one {} away but removed one {} away", farthest_kept, closest_removed);
+    }
+}
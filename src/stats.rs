@@ -0,0 +1,30 @@
+use serde::{Serialize, Deserialize};
+
+/*Running totals for the current game, shown on the death screen. Saved along with the map*/
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct Stats {
+    pub turns: i32,
+    pub kills: i32,
+}
+
+pub const MAX_NAME_LENGTH : usize = 16;
+const DEFAULT_NAME : &str = "Adventurer";
+
+/*What the player called their character at the start of the game. Shown in the panel and on the death screen, and saved with the game*/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerName {
+    pub name: String,
+}
+
+impl Default for PlayerName { /*Also what saves from before names were a thing get*/
+    fn default() -> PlayerName {
+        PlayerName { name: DEFAULT_NAME.to_string() }
+    }
+}
+
+impl PlayerName {
+    pub fn new(entered: &str) -> PlayerName { /*Leaving it blank gets the default*/
+        let name : String = entered.trim().chars().take(MAX_NAME_LENGTH).collect();
+        if name.is_empty() { PlayerName::default() } else { PlayerName { name } }
+    }
+}
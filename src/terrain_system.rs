@@ -0,0 +1,27 @@
+use specs::prelude::*;
+use super::{Map, TileType, Position, CombatStats, SufferDamage, Name, DamageType, gamelog::{GameLog, Logger}};
+
+const LAVA_DAMAGE : i32 = 6; /*Each turn spent standing in it. Resisting fire halves it like any other burn*/
+
+pub struct TerrainSystem {}
+
+impl<'a> System<'a> for TerrainSystem { /*Whatever the ground does to anything standing on it at the end of the turn. For now that's just lava burning*/
+    type SystemData = ( ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteExpect<'a, GameLog>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (map, entities, positions, combat_stats, names, mut suffer_damage, mut log) = data;
+
+        for (entity, pos, stats) in (&entities, &positions, &combat_stats).join() {
+            if stats.hp < 1 || map.tiles[map.xy_idx(pos.x, pos.y)] != TileType::Lava { continue; }
+            let name = names.get(entity).map_or("Something", |n| n.name.as_str());
+            Logger::new().npc_name(name).append("is burned by the lava").log(&mut log);
+            SufferDamage::new_damage(&mut suffer_damage, entity, LAVA_DAMAGE, DamageType::Fire);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use specs::prelude::*;
+use rltk::RGB;
+use super::{EntryTrigger, Hidden, SingleActivation, InflictsDamage, SufferDamage, Name, Map, Size,
+    gamelog::{GameLog, Logger}, particle_system::ParticleBuilder, deletion_queue::ToDelete, movement::{MovedChannel, MovedReaders}};
+
+pub struct TriggerSystem {}
+
+impl<'a> System<'a> for TriggerSystem { /*Sets off whatever is on the tile of anything that moved since it last ran. Runs after indexing, so tile_content is up to date*/
+    type SystemData = ( ReadExpect<'a, Map>,
+                        ReadExpect<'a, MovedChannel>,
+                        WriteExpect<'a, MovedReaders>,
+                        ReadStorage<'a, EntryTrigger>,
+                        WriteStorage<'a, Hidden>,
+                        ReadStorage<'a, SingleActivation>,
+                        ReadStorage<'a, InflictsDamage>,
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Size>,
+                        Entities<'a>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        WriteExpect<'a, ToDelete>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (map, moved, mut readers, entry_trigger, mut hidden, single_activation, inflicts_damage, mut suffer_damage, names, sizes, entities,
+            mut log, mut particle_builder, mut to_delete) = data;
+
+        for event in moved.read(&mut readers.triggers) {
+            let entity = event.entity;
+            if !entities.is_alive(entity) { continue; } /*Moved, then died, before the traps got a look*/
+            let size = sizes.get(entity).unwrap_or(&Size::SINGLE);
+            for (x, y) in size.tiles(event.x, event.y) { /*Something big sets off whatever is under any part of it*/
+                if !map.in_bounds(x, y) { continue; }
+                let idx = map.xy_idx(x, y);
+                for trap in map.tile_content[idx].iter() {
+                    if entity == *trap || entry_trigger.get(*trap).is_none() { continue; } /*Only traps, and nothing sets itself off*/
+
+                    hidden.remove(*trap); /*Whatever just happened, everybody knows where the trap is now*/
+                    let victim_name = names.get(entity).map_or("Something", |n| n.name.as_str());
+                    let trap_name = names.get(*trap).map_or("a trap", |n| n.name.as_str());
+                    Logger::new().npc_name(victim_name).append("triggers").item_name(trap_name).log(&mut log);
+
+                    if let Some(damage) = inflicts_damage.get(*trap) {
+                        SufferDamage::new_damage(&mut suffer_damage, entity, damage.damage, damage.damage_type);
+                        particle_builder.request(x, y, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), rltk::to_cp437('‼'), 200.0);
+                    }
+                    if single_activation.get(*trap).is_some() { to_delete.push(*trap); }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Position, DamageType, map::TileType, movement::{self, MovedEvent}, saveload_system::register_components, map_indexing_system::MapIndexingSystem};
+
+    #[test]
+    fn a_big_mover_sets_off_a_trap_under_any_part_of_it() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        movement::register(&mut ecs);
+        ecs.insert(GameLog::new("Start"));
+        ecs.insert(ParticleBuilder::new());
+        ecs.insert(ToDelete::default());
+        let tile_count = 36;
+        ecs.insert(Map { tiles: vec![TileType::Floor; tile_count], blocked: vec![false; tile_count], tile_content: vec![Vec::new(); tile_count], width: 6, height: 6, ..Default::default() });
+        let trap = ecs.create_entity().with(Position { x: 3, y: 3 }).with(EntryTrigger {}).with(InflictsDamage { damage: 4, damage_type: DamageType::Physical }).build();
+        let ogre = ecs.create_entity().with(Position { x: 2, y: 2 }).with(Size { width: 2, height: 2 }).build(); /*Its far corner is on the trap*/
+        MapIndexingSystem{}.run_now(&ecs);
+        ecs.write_resource::<movement::MovedChannel>().single_write(MovedEvent { entity: ogre, x: 2, y: 2 });
+
+        TriggerSystem{}.run_now(&ecs);
+        let damage = ecs.read_storage::<SufferDamage>();
+        assert_eq!(damage.get(ogre).map(|hurt| hurt.amount.iter().map(|(amount, _kind)| *amount).collect::<Vec<_>>()), Some(vec![4]));
+        assert!(damage.get(trap).is_none());
+    }
+}
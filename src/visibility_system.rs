@@ -0,0 +1,174 @@
+use specs::prelude::*;
+use super::{Viewshed, Position, Map, lights_up, Player, BlocksVisibility, LightSource, InBackpack, Equipped, movement::{MovedChannel, MovedReaders}};
+use std::collections::HashSet;
+use rltk::{field_of_view, Point, RGB};
+
+const LIT_SIGHT_RANGE : i32 = 16; /*How far away a lit tile can be seen, even by something that can only see a few tiles in the dark*/
+
+pub struct VisibilitySystem {}
+
+impl<'a> System<'a> for VisibilitySystem { /*Works out which tiles each entity with a viewshed can see*/
+    type SystemData = ( WriteExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteStorage<'a, Viewshed>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Player>,
+                        ReadStorage<'a, BlocksVisibility>,
+                        ReadStorage<'a, LightSource>,
+                        ReadStorage<'a, InBackpack>,
+                        ReadStorage<'a, Equipped>,
+                        ReadExpect<'a, MovedChannel>,
+                        WriteExpect<'a, MovedReaders>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (mut map, entities, mut viewshed, pos, player, blocks_visibility, light_sources, backpack, equipped, moved, mut readers) = data;
+
+        for event in moved.read(&mut readers.visibility) { /*Anything that moved sees from somewhere new*/
+            if let Some(viewshed) = viewshed.get_mut(event.entity) { viewshed.dirty = true; }
+        }
+
+        let view_blocked : HashSet<usize> = (&pos, &blocks_visibility).join() /*Where the things that block sight are standing right now*/
+            .filter(|(pos, _blocks)| map.in_bounds(pos.x, pos.y))
+            .map(|(pos, _blocks)| map.xy_idx(pos.x, pos.y))
+            .collect();
+        let sight_changed = view_blocked != map.view_blocked;
+        if sight_changed { /*Something that blocks sight moved, appeared or went away, so everyone has to look again*/
+            map.view_blocked = view_blocked;
+            for viewshed in (&mut viewshed).join() {
+                viewshed.dirty = true;
+            }
+        }
+
+        let lit_by : Vec<(Point, i32, RGB)> = (&entities, &light_sources).join()
+            .filter_map(|(entity, source)| {
+                let holder = backpack.get(entity).map(|pack| pack.owner).or_else(|| equipped.get(entity).map(|gear| gear.owner)); /*Carried lights shine from whoever is carrying them*/
+                pos.get(holder.unwrap_or(entity)).map(|p| (Point::new(p.x, p.y), source.radius, source.color))
+            })
+            .collect();
+        if sight_changed || map.lit_by.as_ref() != Some(&lit_by) { /*Light only needs working out again when a light moved or something new is in its way*/
+            let mut light = vec![RGB::from_f32(0.0, 0.0, 0.0); map.tiles.len()];
+            for (centre, radius, color) in lit_by.iter().copied() {
+                for tile in field_of_view(centre, radius, &*map).iter().filter(|p| map.in_bounds(p.x, p.y)) { /*Walls stop light the same way they stop sight*/
+                    let distance = rltk::DistanceAlg::Pythagoras.distance2d(centre, *tile);
+                    let strength = f32::max(0.0, 1.0 - distance / (radius + 1) as f32); /*Fades out towards the edge*/
+                    let idx = map.xy_idx(tile.x, tile.y);
+                    light[idx] = light[idx] + color * strength;
+                }
+            }
+            map.lit_by = Some(lit_by);
+            let switched : Vec<Point> = light.iter().enumerate() /*Tiles that went from dark to lit or back. Brighter or a different colour doesn't change what can be seen*/
+                .filter(|(idx, tile_light)| lights_up(tile_light) != map.is_lit(*idx))
+                .map(|(idx, _tile_light)| { let (x, y) = map.idx_xy(idx); Point::new(x, y) })
+                .collect();
+            map.light = light;
+            for (viewshed, pos) in (&mut viewshed, &pos).join() { /*Only things close enough to see one of those tiles by its light have to look again*/
+                let centre = Point::new(pos.x, pos.y);
+                let reach = i32::max(viewshed.range, LIT_SIGHT_RANGE) as f32;
+                if switched.iter().any(|tile| {
+                    let distance = rltk::DistanceAlg::Pythagoras.distance2d(centre, *tile);
+                    distance > viewshed.range as f32 && distance <= reach /*Anything nearer is seen lit or not*/
+                }) {
+                    viewshed.dirty = true;
+                }
+            }
+        }
+
+        let mut lit_tiles : Option<Vec<Point>> = None; /*Only gathered once something has to look*/
+        for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
+            if viewshed.dirty { /*Only recompute when something has moved, field of view is not free*/
+                viewshed.dirty = false;
+                viewshed.visible_tiles.clear();
+                let centre = Point::new(pos.x, pos.y);
+                let lit_tiles = lit_tiles.get_or_insert_with(|| (0..map.light.len()).filter(|idx| map.is_lit(*idx)).map(|idx| { let (x, y) = map.idx_xy(idx); Point::new(x, y) }).collect());
+                let lit_beyond_range = lit_tiles.iter().any(|tile| {
+                    let distance = rltk::DistanceAlg::Pythagoras.distance2d(centre, *tile);
+                    distance > viewshed.range as f32 && distance <= LIT_SIGHT_RANGE as f32
+                });
+                let radius = if lit_beyond_range { i32::max(viewshed.range, LIT_SIGHT_RANGE) } else { viewshed.range }; /*Looking further is much more work, so only when there is light out there to see by*/
+                viewshed.visible_tiles = field_of_view(centre, radius, &*map);
+                viewshed.visible_tiles.retain(|p| map.in_bounds(p.x, p.y)); /*Field of view can return points off the edge of the map, throw those away*/
+                let range = viewshed.range as f32;
+                viewshed.visible_tiles.retain(|p| rltk::DistanceAlg::Pythagoras.distance2d(centre, *p) <= range || map.is_lit(map.xy_idx(p.x, p.y))); /*Further out than that, only lit tiles show up*/
+
+                if player.get(ent).is_some() { /*Only the player's viewshed reveals the map*/
+                    for t in map.visible_tiles.iter_mut() { *t = false }; /*Forget what was visible last turn*/
+                    let visible : Vec<usize> = viewshed.visible_indices(&map).collect();
+                    for idx in visible {
+                        map.revealed_tiles[idx] = true;
+                        map.visible_tiles[idx] = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::TileType, movement::{self, MovedEvent}, saveload_system::register_components};
+
+    const MARK : Point = Point { x: -1, y: -1 }; /*Left in each viewshed between turns. Working out the field of view again throws it away*/
+
+    fn open_world() -> World {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        movement::register(&mut ecs);
+        let (width, height) = (40, 40);
+        let tile_count = (width * height) as usize;
+        ecs.insert(Map { tiles: vec![TileType::Floor; tile_count], revealed_tiles: vec![false; tile_count], visible_tiles: vec![false; tile_count], blocked: vec![false; tile_count],
+            tile_content: vec![Vec::new(); tile_count], light: vec![RGB::from_f32(0.0, 0.0, 0.0); tile_count], width, height, ..Default::default() });
+        ecs
+    }
+
+    fn looker(ecs: &mut World, x: i32, y: i32) -> Entity {
+        ecs.create_entity().with(Position { x, y }).with(Viewshed { visible_tiles: Vec::new(), range: 8, dirty: true }).build()
+    }
+
+    fn looked_again(ecs: &mut World, lookers: &[Entity]) -> Vec<bool> { /*Runs a turn's visibility, and says which of them worked out their field of view again*/
+        for viewshed in (&mut ecs.write_storage::<Viewshed>()).join() { viewshed.visible_tiles.push(MARK); }
+        VisibilitySystem{}.run_now(ecs);
+        let viewsheds = ecs.read_storage::<Viewshed>();
+        lookers.iter().map(|looker| !viewsheds.get(*looker).expect("No viewshed").visible_tiles.contains(&MARK)).collect()
+    }
+
+    #[test]
+    fn idle_turns_do_not_look_again_and_a_carried_light_only_wakes_those_near_it() {
+        let mut ecs = open_world();
+        let player = looker(&mut ecs, 5, 5);
+        ecs.write_storage::<Player>().insert(player, Player {}).expect("Unable to insert");
+        ecs.create_entity().with(InBackpack { owner: player }).with(LightSource { radius: 3, color: RGB::named(rltk::YELLOW) }).build();
+        let close = looker(&mut ecs, 8, 5); /*Sees all of the light's footprint in the dark anyway*/
+        let across = looker(&mut ecs, 17, 5); /*Only sees the light's far edge because it is lit*/
+        let far = looker(&mut ecs, 35, 35); /*Too far to see any of it*/
+        let lookers = [player, close, across, far];
+        assert_eq!(looked_again(&mut ecs, &lookers), [true; 4], "everything looks on the first turn");
+
+        let mut again = 0;
+        for _turn in 0..10 {
+            again += looked_again(&mut ecs, &lookers).iter().filter(|looked| **looked).count();
+        }
+        assert_eq!(again, 0, "nothing moved, so nobody should look again");
+
+        ecs.write_storage::<Position>().insert(player, Position { x: 6, y: 5 }).expect("Unable to insert");
+        ecs.write_resource::<movement::MovedChannel>().single_write(MovedEvent { entity: player, x: 6, y: 5 });
+        assert_eq!(looked_again(&mut ecs, &lookers), [true, false, true, false], "the player moved, and so did the light they carry");
+        assert!(ecs.fetch::<Map>().is_lit(ecs.fetch::<Map>().xy_idx(9, 5)));
+        assert_eq!(looked_again(&mut ecs, &lookers), [false; 4], "and idle again after");
+    }
+
+    #[test]
+    fn lit_tiles_are_seen_from_further_than_dark_ones() {
+        let mut ecs = open_world();
+        let viewer = looker(&mut ecs, 5, 5);
+        looked_again(&mut ecs, &[viewer]);
+        let furthest = |ecs: &World| ecs.read_storage::<Viewshed>().get(viewer).expect("No viewshed").visible_tiles.iter().map(|p| p.x).max();
+        assert_eq!(furthest(&ecs), Some(13), "in the dark only as far as its range");
+
+        ecs.create_entity().with(Position { x: 18, y: 5 }).with(LightSource { radius: 2, color: RGB::named(rltk::WHITE) }).build();
+        looked_again(&mut ecs, &[viewer]);
+        assert_eq!(furthest(&ecs), Some(20), "the lit patch further off shows up too");
+        let viewsheds = ecs.read_storage::<Viewshed>();
+        assert!(!viewsheds.get(viewer).expect("No viewshed").visible_tiles.contains(&Point::new(15, 0)), "but not the dark between");
+    }
+}